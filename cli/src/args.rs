@@ -1,4 +1,4 @@
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
@@ -26,6 +26,52 @@ pub struct CliArguments {
     /// How much raster images of rasterized effects should be scaled up.
     #[clap(long, default_value = "1.5")]
     pub raster_scale: f32,
+    /// The maximum number of pixels of the image a rasterized effect is rendered into.
+    /// If exceeded, the raster scale of that effect is lowered.
+    #[clap(long, default_value = "16777216", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_pixmap_area: u32,
+    /// Encode rasterized effects as JPEG images with the given quality (1-100)
+    /// instead of losslessly.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub raster_jpeg_quality: Option<u8>,
+    /// Resample raster images whose resolution at their placed size exceeds
+    /// the given number of pixels per inch.
+    #[clap(long)]
+    pub max_image_dpi: Option<f32>,
+    /// The filter with which images are resampled.
+    #[clap(long, value_enum, default_value_t = ResamplingFilter::Lanczos3)]
+    pub image_resampling: ResamplingFilter,
+    /// Scale up raster images with `image-rendering="optimizeSpeed"` whose
+    /// resolution at their placed size is lower than the given number of pixels
+    /// per inch, so that they stay crisp in all viewers.
+    #[clap(long)]
+    pub pixelated_image_dpi: Option<f32>,
+    /// Whether the PDF should conform to the PDF/A-2b standard.
+    #[clap(long, action=ArgAction::SetTrue)]
+    pub pdfa: bool,
+    /// Whether to write a tagged PDF for assistive technology. Titles,
+    /// descriptions and ARIA labels of images and groups with an id become
+    /// their alternative texts.
+    #[clap(long, action=ArgAction::SetTrue)]
+    pub tagged: bool,
+    /// Whether parts of the SVG that cannot be converted should be skipped
+    /// instead of aborting the conversion.
+    #[clap(long, action=ArgAction::SetTrue)]
+    pub lenient: bool,
+    /// Path to a CMYK ICC profile that colors should be converted into,
+    /// e.g. for printing.
+    #[clap(long)]
+    pub cmyk_profile: Option<PathBuf>,
+}
+
+/// A filter for resampling raster images.
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum ResamplingFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
 }
 
 // What to do.
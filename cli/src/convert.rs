@@ -27,7 +27,19 @@ pub fn convert_(
 
     let svg = std::fs::read_to_string(input).map_err(|_| "Failed to load SVG file")?;
 
-    let options = usvg::Options::default();
+    // The title, the language and the alternative texts of the SVG are not
+    // retained in the usvg tree.
+    let mut conversion_options = ConversionOptions {
+        alt_texts: svg2pdf::alt_texts_from_svg(&svg),
+        ..conversion_options
+    };
+    conversion_options.metadata.fill_from_svg(&svg);
+
+    let options = usvg::Options {
+        #[cfg(feature = "image")]
+        image_href_resolver: svg2pdf::image_href_resolver(),
+        ..usvg::Options::default()
+    };
 
     let tree = usvg::Tree::from_str(
         &svg,
@@ -43,7 +55,8 @@ pub fn convert_(
         page_options,
         #[cfg(feature = "text")]
         &fontdb,
-    );
+    )
+    .map_err(|err| err.to_string())?;
 
     std::fs::write(output, pdf).map_err(|_| "Failed to write PDF file")?;
 
@@ -2,13 +2,14 @@ mod args;
 mod convert;
 mod fonts;
 
-use crate::args::{CliArguments, Command};
+use crate::args::{CliArguments, Command, ResamplingFilter};
 use clap::Parser;
 use std::{
+    collections::HashMap,
     io::{self, Write},
     process,
 };
-use svg2pdf::{ConversionOptions, PageOptions};
+use svg2pdf::{ConversionOptions, Metadata, PageOptions};
 use termcolor::{ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 fn main() {
@@ -23,10 +24,29 @@ fn run() -> Result<(), String> {
 
     // If an input argument was provided, convert the svg file to pdf.
     if let Some(input) = args.input {
+        let cmyk_profile = args
+            .cmyk_profile
+            .map(std::fs::read)
+            .transpose()
+            .map_err(|_| "Failed to load CMYK profile")?;
+
         let conversion_options = ConversionOptions {
             compress: true,
             embed_text: !args.text_to_paths,
+            #[cfg(feature = "text")]
+            font_cache: None,
             raster_scale: args.raster_scale,
+            max_pixmap_area: args.max_pixmap_area,
+            raster_jpeg_quality: args.raster_jpeg_quality,
+            max_image_dpi: args.max_image_dpi,
+            image_resampling: resampling_filter(args.image_resampling),
+            pixelated_image_dpi: args.pixelated_image_dpi,
+            metadata: Metadata::default(),
+            tagged: args.tagged,
+            alt_texts: HashMap::new(),
+            pdfa: args.pdfa,
+            lenient: args.lenient,
+            cmyk_profile,
         };
 
         let page_options = PageOptions { dpi: args.dpi };
@@ -46,6 +66,16 @@ fn run() -> Result<(), String> {
     Ok(())
 }
 
+fn resampling_filter(filter: ResamplingFilter) -> svg2pdf::ResamplingFilter {
+    match filter {
+        ResamplingFilter::Nearest => svg2pdf::ResamplingFilter::Nearest,
+        ResamplingFilter::Triangle => svg2pdf::ResamplingFilter::Triangle,
+        ResamplingFilter::CatmullRom => svg2pdf::ResamplingFilter::CatmullRom,
+        ResamplingFilter::Gaussian => svg2pdf::ResamplingFilter::Gaussian,
+        ResamplingFilter::Lanczos3 => svg2pdf::ResamplingFilter::Lanczos3,
+    }
+}
+
 fn print_error(msg: &str) -> io::Result<()> {
     let mut w = StandardStream::stderr(ColorChoice::Always);
 
@@ -5,9 +5,10 @@ either standalone PDF files or Form XObjects that can be embedded in another
 PDF file and used just like images.
 
 The conversion will translate the SVG content to PDF without rasterizing them
-(the only exception being objects with filters on them, but in this case only
-this single group will be rasterized, while the remaining contents of the SVG
-will still be turned into a vector graphic), so no quality is lost.
+(the only exception being objects with filters on them that cannot be expressed
+with vector graphics, but in this case only this single group will be rasterized,
+while the remaining contents of the SVG will still be turned into a vector graphic),
+so no quality is lost.
 
 ## Example
 This example reads an SVG file and writes the corresponding PDF back to the disk.
@@ -26,7 +27,7 @@ let mut db = fontdb::Database::new();
 db.load_system_fonts();
 let tree = svg2pdf::usvg::Tree::from_str(&svg, &options, &db)?;
 
-let pdf = svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default(), &db);
+let pdf = svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default(), &db)?;
 std::fs::write(output, pdf)?;
 # Ok(()) }
 ```
@@ -42,15 +43,15 @@ but not limited to:
 - Transformations
 - Viewbox
 - Text
-- Raster images and nested SVGs
+- Raster images (color managed using their embedded ICC profiles) and nested SVGs
+- WebP, TIFF and BMP images when parsing with [`image_href_resolver`] and enabling the
+  `webp`, `tiff` and `bmp` features
 
 ## Unsupported features
 Among the unsupported features are currently:
-- The `spreadMethod` attribute of gradients
-- Raster images are not color managed but use PDF's DeviceRGB color space
 - A number of features that were added in SVG2, See
-[here](https://github.com/RazrFalcon/resvg/blob/master/docs/svg2-changelog.md) for a more
-comprehensive list.
+  [here](https://github.com/RazrFalcon/resvg/blob/master/docs/svg2-changelog.md) for a more
+  comprehensive list.
  */
 
 mod render;
@@ -58,16 +59,29 @@ mod util;
 
 pub use usvg;
 
+#[cfg(feature = "text")]
+pub use crate::util::font_cache::FontCache;
+pub use crate::util::metadata::{DateTime, Metadata};
+#[cfg(feature = "image")]
+pub use crate::util::resolver::image_href_resolver;
+pub use crate::util::tags::alt_texts_from_svg;
+
+use std::collections::HashMap;
+
 use once_cell::sync::Lazy;
-use pdf_writer::{Chunk, Content, Filter, Finish, Pdf, Ref, TextStr};
+use pdf_writer::types::OutputIntentSubtype;
+use pdf_writer::writers::OutputIntent;
+use pdf_writer::{Chunk, Content, Filter, Finish, Name, Pdf, Ref, TextStr};
 #[cfg(feature = "text")]
 use usvg::fontdb;
 use usvg::{Size, Transform, Tree};
 
 use crate::render::{tree_to_stream, tree_to_xobject};
 use crate::util::context::Context;
-use crate::util::helper::{deflate, RectExt, TransformExt};
+use crate::util::helper::{deflate, hash128, RectExt, TransformExt};
+use crate::util::metadata;
 use crate::util::resources::ResourceContainer;
+use crate::util::tags::StreamTags;
 
 // The ICC profiles.
 static SRGB_ICC_DEFLATED: Lazy<Vec<u8>> =
@@ -91,7 +105,7 @@ impl Default for PageOptions {
 }
 
 /// Options for the PDF conversion.
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct ConversionOptions {
     /// Whether the content streams should be compressed.
     ///
@@ -109,12 +123,135 @@ pub struct ConversionOptions {
     /// _Default:_ 1.5
     pub raster_scale: f32,
 
+    /// The maximum number of pixels of the image a rasterized effect is rendered
+    /// into.
+    ///
+    /// If an effect would exceed it with the configured
+    /// [`raster_scale`](ConversionOptions::raster_scale), e.g. because it has a huge
+    /// filter region, the raster scale is lowered for that effect and a warning is
+    /// logged. This prevents huge allocations for such regions. A value of zero is
+    /// treated like one pixel.
+    ///
+    /// _Default:_ 16777216 (4096 x 4096 pixels)
+    pub max_pixmap_area: u32,
+
+    /// The JPEG quality (1-100) with which rasterized effects are encoded.
+    ///
+    /// If set, the color channels of rasterized effects are written as JPEG
+    /// images, which can be much smaller than lossless images for photographic
+    /// content such as blurs. The alpha channel is always written losslessly. If
+    /// unset, rasterized effects are compressed losslessly.
+    ///
+    /// _Default:_ `None`
+    pub raster_jpeg_quality: Option<u8>,
+
+    /// The maximum effective resolution of raster images, in pixels per inch at the
+    /// size they are placed at on the page.
+    ///
+    /// Images with a higher resolution are resampled with the
+    /// [`image_resampling`](ConversionOptions::image_resampling) filter before they
+    /// are embedded, which can drastically reduce the size of the PDF. An image that
+    /// is placed at different sizes is embedded once per size. [`to_chunk`] assumes
+    /// that one unit of the SVG canvas ends up as one point. Images inside of
+    /// patterns, masks and nested SVG images are never resampled, since their
+    /// final size is not known.
+    ///
+    /// _Default:_ `None`
+    pub max_image_dpi: Option<f32>,
+
+    /// The filter that is used when resampling images to the
+    /// [maximum resolution](ConversionOptions::max_image_dpi).
+    ///
+    /// _Default:_ [`ResamplingFilter::Lanczos3`]
+    pub image_resampling: ResamplingFilter,
+
+    /// The minimum effective resolution of raster images with
+    /// `image-rendering="optimizeSpeed"`, in pixels per inch at the size they are
+    /// placed at on the page.
+    ///
+    /// Such images are written with `/Interpolate false`, but not all viewers
+    /// respect it and smooth them anyway. If set, images with a lower resolution are
+    /// scaled up by a whole number with nearest-neighbor sampling before they are
+    /// embedded, so that their pixels stay crisp in these viewers. The same
+    /// restrictions as for [`max_image_dpi`](ConversionOptions::max_image_dpi)
+    /// apply. Images are never scaled up beyond the maximum resolution or to more
+    /// pixels than the [`max_pixmap_area`](ConversionOptions::max_pixmap_area).
+    ///
+    /// _Default:_ `None`
+    pub pixelated_image_dpi: Option<f32>,
+
     /// Whether text should be embedded as actual selectable text inside
     /// the PDF. If this option is disabled, text will be converted into paths
     /// before rendering.
     ///
     /// _Default:_ `true`.
     pub embed_text: bool,
+
+    /// A cache for the fonts of embedded text, which allows reusing them across
+    /// conversions that use the same fonts.
+    ///
+    /// _Default:_ `None`, i.e. the fonts are loaded for each conversion.
+    #[cfg(feature = "text")]
+    pub font_cache: Option<FontCache>,
+
+    /// The metadata of the document. Only used when writing a standalone PDF.
+    ///
+    /// Use [`Metadata::fill_from_svg`] to fill in a missing title and language from
+    /// the SVG source, since they are not part of the [`usvg` tree](Tree).
+    ///
+    /// _Default:_ empty metadata.
+    pub metadata: Metadata,
+
+    /// Whether to write a tagged PDF with a structure tree, which makes the
+    /// document accessible to assistive technology. Only used when writing a
+    /// standalone PDF.
+    ///
+    /// Text becomes paragraphs with a span for each of its text spans. Images and
+    /// groups with an [alternative text](ConversionOptions::alt_texts) become
+    /// figures, whose content isn't tagged any further. All other paths and images,
+    /// rasterized effects and the content of patterns and masks are marked as
+    /// decorative artifacts.
+    ///
+    /// _Default:_ `false`.
+    pub tagged: bool,
+
+    /// The alternative texts of the nodes of the tree, keyed by their id. Only used
+    /// for [tagged PDFs](ConversionOptions::tagged).
+    ///
+    /// Use [`alt_texts_from_svg`] to fill them in from the titles, descriptions and
+    /// ARIA labels in the SVG source, since they are not part of the [`usvg`
+    /// tree](Tree).
+    ///
+    /// _Default:_ empty.
+    pub alt_texts: HashMap<String, String>,
+
+    /// Whether the resulting PDF should conform to the PDF/A-2b standard.
+    ///
+    /// In this mode, an output intent with the sRGB profile (or the [CMYK output
+    /// profile](ConversionOptions::cmyk_profile), if one was given) and the PDF/A
+    /// identification are written, and only ICC-based color spaces are used. If
+    /// the SVG contains a construct that cannot be represented in a conforming way,
+    /// the conversion will fail with a [`ConversionError::PdfA`]. Only used when
+    /// writing a standalone PDF.
+    ///
+    /// _Default:_ `false`.
+    pub pdfa: bool,
+
+    /// Whether nodes that cannot be converted, e.g. because they contain a corrupt
+    /// image, should be skipped instead of failing the whole conversion. A warning
+    /// is logged for each skipped node.
+    ///
+    /// _Default:_ `false`.
+    pub lenient: bool,
+
+    /// A CMYK ICC profile of the output device, e.g. a printing press.
+    ///
+    /// If set, solid colors and gradients are converted into this profile and
+    /// it is written as the output intent of the document. Raster images keep
+    /// their own color space.
+    ///
+    /// _Default:_ `None`.
+    pub cmyk_profile: Option<Vec<u8>>,
 }
 
 impl Default for ConversionOptions {
@@ -122,16 +259,89 @@ impl Default for ConversionOptions {
         Self {
             compress: false,
             raster_scale: 1.5,
+            max_pixmap_area: 4096 * 4096,
+            raster_jpeg_quality: None,
+            max_image_dpi: None,
+            image_resampling: ResamplingFilter::default(),
+            pixelated_image_dpi: None,
             embed_text: true,
+            #[cfg(feature = "text")]
+            font_cache: None,
+            metadata: Metadata::default(),
+            tagged: false,
+            alt_texts: HashMap::new(),
+            pdfa: false,
+            lenient: false,
+            cmyk_profile: None,
+        }
+    }
+}
+
+/// A filter for resampling raster images.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ResamplingFilter {
+    /// Nearest neighbor sampling. Fast, but blocky.
+    Nearest,
+    /// Linear interpolation.
+    Triangle,
+    /// Cubic interpolation.
+    CatmullRom,
+    /// Gaussian filtering. Slightly blurry.
+    Gaussian,
+    /// Lanczos with a window of 3. Slow, but sharp.
+    #[default]
+    Lanczos3,
+}
+
+/// An error that can occur during the conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// An embedded raster image could not be decoded. Contains the reason
+    /// reported by the decoder.
+    InvalidImage(String),
+    /// A font used in the SVG could not be parsed. Contains the name of the font.
+    InvalidFont(String),
+    /// The tree would result in a page without any area, e.g. because of an
+    /// invalid DPI.
+    ZeroSizedTree,
+    /// The [CMYK output profile](ConversionOptions::cmyk_profile) is not a valid
+    /// CMYK ICC profile. Contains the reason.
+    InvalidColorProfile(String),
+    /// The SVG contains a construct that cannot be written in a way that conforms
+    /// to PDF/A. Contains a description of the problem.
+    PdfA(String),
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidImage(reason) => write!(f, "failed to decode image: {reason}"),
+            Self::InvalidFont(name) => write!(f, "failed to parse font {name}"),
+            Self::ZeroSizedTree => f.write_str("tree has a size of zero"),
+            Self::InvalidColorProfile(reason) => {
+                write!(f, "invalid CMYK output profile: {reason}")
+            }
+            Self::PdfA(reason) => write!(f, "PDF/A conformance violation: {reason}"),
         }
     }
 }
 
+impl std::error::Error for ConversionError {}
+
+/// The result type of the conversion.
+pub type Result<T> = std::result::Result<T, ConversionError>;
+
 /// Convert a [`usvg` tree](Tree) into a standalone PDF buffer.
 ///
 /// IMPORTANT: The fontdb that is passed to this function needs to be the
 /// same one that was used to convert the SVG string into a [`usvg` tree](Tree)!
 ///
+/// Returns an error if the tree cannot be converted, e.g. because it contains a
+/// corrupt image, unless [lenient mode](ConversionOptions::lenient) is enabled.
+/// Independently of that, an error is returned if [PDF/A
+/// conformance](ConversionOptions::pdfa) was requested but the SVG cannot be
+/// converted in a conforming way.
+///
 /// ## Example
 /// The example below reads an SVG file, processes text within it, then converts
 /// it into a PDF and finally writes it back to the file system.
@@ -151,7 +361,7 @@ impl Default for ConversionOptions {
 /// let mut tree = svg2pdf::usvg::Tree::from_str(&svg, &options, &db)?;
 ///
 ///
-/// let pdf = svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default(), &db);
+/// let pdf = svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default(), &db)?;
 /// std::fs::write(output, pdf)?;
 /// # Ok(()) }
 /// ```
@@ -160,37 +370,199 @@ pub fn to_pdf(
     conversion_options: ConversionOptions,
     page_options: PageOptions,
     #[cfg(feature = "text")] fontdb: &fontdb::Database,
-) -> Vec<u8> {
+) -> Result<Vec<u8>> {
+    to_multi_page_pdf(
+        &[(tree, page_options)],
+        conversion_options,
+        #[cfg(feature = "text")]
+        fontdb,
+    )
+}
+
+/// Convert multiple [`usvg` trees](Tree) into a standalone PDF buffer, with each
+/// tree being written as a separate page.
+///
+/// All pages are converted using the same context, so fonts and ICC profiles that
+/// are used on multiple pages are only written once.
+///
+/// IMPORTANT: The fontdb that is passed to this function needs to be the
+/// same one that was used to convert the SVG strings into [`usvg` trees](Tree)!
+///
+/// ## Example
+/// The example below converts two SVG files into a PDF with two pages.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use svg2pdf::usvg::fontdb;
+/// use svg2pdf::{ConversionOptions, PageOptions};
+///
+/// let inputs = [
+///     "tests/svg/custom/integration/matplotlib/stairs.svg",
+///     "tests/svg/custom/integration/matplotlib/signals.svg",
+/// ];
+/// let output = "target/pages.pdf";
+///
+/// let options = svg2pdf::usvg::Options::default();
+/// let mut db = fontdb::Database::new();
+/// db.load_system_fonts();
+///
+/// let mut trees = vec![];
+/// for input in inputs {
+///     let svg = std::fs::read_to_string(input)?;
+///     trees.push(svg2pdf::usvg::Tree::from_str(&svg, &options, &db)?);
+/// }
+///
+/// let pages: Vec<_> = trees.iter().map(|tree| (tree, PageOptions::default())).collect();
+/// let pdf = svg2pdf::to_multi_page_pdf(&pages, ConversionOptions::default(), &db)?;
+/// std::fs::write(output, pdf)?;
+/// # Ok(()) }
+/// ```
+pub fn to_multi_page_pdf(
+    pages: &[(&Tree, PageOptions)],
+    conversion_options: ConversionOptions,
+    #[cfg(feature = "text")] fontdb: &fontdb::Database,
+) -> Result<Vec<u8>> {
+    #[cfg(feature = "text")]
+    let trees: Vec<&Tree> = pages.iter().map(|(tree, _)| *tree).collect();
     let mut ctx = Context::new(
         #[cfg(feature = "text")]
-        tree,
+        &trees,
         conversion_options,
         #[cfg(feature = "text")]
         fontdb,
-    );
+    )?;
+    #[cfg(feature = "image")]
+    {
+        ctx.unique_svg_images =
+            render::image::unique_svg_images(pages.iter().map(|(tree, _)| *tree));
+    }
     let mut pdf = Pdf::new();
 
+    let catalog_ref = ctx.alloc_ref();
+    let page_tree_ref = ctx.alloc_ref();
+    let metadata_ref = ctx.alloc_ref();
+    let page_refs: Vec<Ref> = pages.iter().map(|_| ctx.alloc_ref()).collect();
+    let struct_tree_root_ref = ctx.options.tagged.then(|| ctx.alloc_ref());
+
+    if ctx.options.pdfa {
+        ctx.check_pdfa()?;
+    }
+
+    let cmyk_ref = ctx.cmyk.as_ref().map(|cmyk| cmyk.reference);
+
+    // If there are multiple output intents, they need to share the same profile.
+    let mut output_intents = vec![];
+    if let Some(cmyk_ref) = cmyk_ref {
+        output_intents.push((OutputIntentSubtype::PDFX, cmyk_ref));
+    }
+    if ctx.options.pdfa {
+        let profile_ref = cmyk_ref.unwrap_or_else(|| ctx.srgb_ref());
+        output_intents.push((OutputIntentSubtype::PDFA, profile_ref));
+    }
+
+    let mut catalog = pdf.catalog(catalog_ref);
+    catalog.pages(page_tree_ref).metadata(metadata_ref);
+    if let Some(language) = &ctx.options.metadata.language {
+        catalog.lang(TextStr(language));
+    }
+
+    if let Some(struct_tree_root_ref) = struct_tree_root_ref {
+        catalog.mark_info().marked(true);
+        catalog.pair(Name(b"StructTreeRoot"), struct_tree_root_ref);
+    }
+
+    if !output_intents.is_empty() {
+        let mut array = catalog.insert(Name(b"OutputIntents")).array();
+        for (subtype, profile_ref) in output_intents {
+            let mut output_intent = array.push().start::<OutputIntent>();
+            output_intent.subtype(subtype).dest_output_profile(profile_ref);
+            if Some(profile_ref) != cmyk_ref {
+                output_intent
+                    .output_condition_identifier(TextStr("sRGB"))
+                    .registry_name(TextStr("http://www.color.org"))
+                    .info(TextStr("sRGB IEC61966-2.1"));
+            } else if let Some(cmyk) = &ctx.cmyk {
+                // The identifier isn't a registered one, so the condition is
+                // described by the profile itself.
+                output_intent
+                    .output_condition_identifier(TextStr("Custom"))
+                    .info(TextStr(&cmyk.description));
+            }
+        }
+    }
+    catalog.finish();
+    pdf.pages(page_tree_ref)
+        .count(page_refs.len() as i32)
+        .kids(page_refs.iter().copied());
+
+    for (&(tree, page_options), &page_ref) in pages.iter().zip(&page_refs) {
+        write_page(tree, page_options, page_ref, page_tree_ref, &mut pdf, &mut ctx)?;
+    }
+
+    if let Some(struct_tree_root_ref) = struct_tree_root_ref {
+        ctx.tags.write(&mut pdf, struct_tree_root_ref, &mut ctx.ref_allocator);
+    }
+
+    ctx.write_global_objects(&mut pdf);
+
+    let document_info_id = ctx.alloc_ref();
+    let metadata = &ctx.options.metadata;
+    metadata::write_document_info(&mut pdf, document_info_id, metadata);
+    metadata::write_xmp_metadata(&mut pdf, metadata_ref, metadata, ctx.options.pdfa);
+
+    if ctx.options.pdfa {
+        // PDF/A requires a document ID, so we derive one from the contents.
+        let id = hash128(pdf.as_bytes()).to_be_bytes().to_vec();
+        pdf.set_file_id((id.clone(), id));
+    }
+
+    Ok(pdf.finish())
+}
+
+/// Write a tree as a single page into a PDF.
+fn write_page(
+    tree: &Tree,
+    page_options: PageOptions,
+    page_ref: Ref,
+    page_tree_ref: Ref,
+    pdf: &mut Pdf,
+    ctx: &mut Context,
+) -> Result<()> {
     let dpi_ratio = 72.0 / page_options.dpi;
     let dpi_transform = Transform::from_scale(dpi_ratio, dpi_ratio);
     let page_size =
         Size::from_wh(tree.size().width() * dpi_ratio, tree.size().height() * dpi_ratio)
-            .unwrap();
+            .ok_or(ConversionError::ZeroSizedTree)?;
+
+    // PDF/A inherits the implementation limits of PDF 1.7, which restrict the
+    // page size.
+    const PDFA_PAGE_SIZE: std::ops::RangeInclusive<f32> = 3.0..=14400.0;
+    if ctx.options.pdfa
+        && !(PDFA_PAGE_SIZE.contains(&page_size.width())
+            && PDFA_PAGE_SIZE.contains(&page_size.height()))
+    {
+        return Err(ConversionError::PdfA(format!(
+            "page size of {}x{}pt is outside of the allowed range from 3 to 14400pt",
+            page_size.width(),
+            page_size.height()
+        )));
+    }
 
-    let catalog_ref = ctx.alloc_ref();
-    let page_tree_ref = ctx.alloc_ref();
-    let page_ref = ctx.alloc_ref();
     let content_ref = ctx.alloc_ref();
 
-    pdf.catalog(catalog_ref).pages(page_tree_ref);
-    pdf.pages(page_tree_ref).count(1).kids([page_ref]);
-
     // Generate main content
     let mut rc = ResourceContainer::new();
+    if ctx.options.tagged {
+        rc.tags = Some(StreamTags::new(None));
+        ctx.tags.page = Some(page_ref);
+    }
+
     let mut content = Content::new();
     content.save_state();
     content.transform(dpi_transform.to_pdf_transform());
-    tree_to_stream(tree, &mut pdf, &mut content, &mut ctx, &mut rc);
+    tree_to_stream(tree, pdf, &mut content, ctx, Some(dpi_transform), &mut rc)?;
     content.restore_state();
+    let struct_parents = rc.tags.take().and_then(|tags| ctx.tags.finish_stream(tags));
     let content_stream = ctx.finish_content(content);
     let mut stream = pdf.stream(content_ref, &content_stream);
 
@@ -211,16 +583,14 @@ pub fn to_pdf(
         .isolated(true)
         .knockout(false)
         .color_space()
-        .icc_based(ctx.srgb_ref());
+        .icc_based(ctx.color_space_ref());
     page.contents(content_ref);
+    if let Some(key) = struct_parents {
+        page.struct_parents(key);
+    }
     page.finish();
 
-    ctx.write_global_objects(&mut pdf);
-
-    let document_info_id = ctx.alloc_ref();
-    pdf.document_info(document_info_id).producer(TextStr("svg2pdf"));
-
-    pdf.finish()
+    Ok(())
 }
 
 /// Convert a [Tree] into a [`Chunk`].
@@ -239,6 +609,9 @@ pub fn to_pdf(
 /// The resulting object can be used by embedding the chunk into your existing chunk
 /// and renumbering it appropriately.
 ///
+/// Returns an error if the tree cannot be converted, e.g. because it contains a
+/// corrupt image, unless [lenient mode](ConversionOptions::lenient) is enabled.
+///
 /// ## Example
 /// Write a PDF file with some text and an SVG graphic.
 ///
@@ -265,7 +638,7 @@ pub fn to_pdf(
 /// let mut db = fontdb::Database::new();
 /// db.load_system_fonts();
 /// let tree = svg2pdf::usvg::Tree::from_str(&svg, &svg2pdf::usvg::Options::default(), &db)?;
-/// let (mut svg_chunk, svg_id) = svg2pdf::to_chunk(&tree, svg2pdf::ConversionOptions::default(), &db);
+/// let (mut svg_chunk, svg_id) = svg2pdf::to_chunk(&tree, svg2pdf::ConversionOptions::default(), &db)?;
 ///
 /// // Renumber the chunk so that we can embed it into our existing workflow, and also make sure
 /// // to update `svg_id`.
@@ -324,17 +697,23 @@ pub fn to_chunk(
     tree: &Tree,
     conversion_options: ConversionOptions,
     #[cfg(feature = "text")] fontdb: &fontdb::Database,
-) -> (Chunk, Ref) {
+) -> Result<(Chunk, Ref)> {
     let mut chunk = Chunk::new();
 
     let mut ctx = Context::new(
         #[cfg(feature = "text")]
-        tree,
+        &[tree],
         conversion_options,
         #[cfg(feature = "text")]
         fontdb,
-    );
-    let x_ref = tree_to_xobject(tree, &mut chunk, &mut ctx);
+    )?;
+    #[cfg(feature = "image")]
+    {
+        ctx.unique_svg_images = render::image::unique_svg_images([tree]);
+    }
+    // We don't know at which size the XObject is placed, so we assume one point per
+    // unit of the canvas.
+    let x_ref = tree_to_xobject(tree, &mut chunk, &mut ctx, Some(Transform::default()))?;
     ctx.write_global_objects(&mut chunk);
-    (chunk, x_ref)
+    Ok((chunk, x_ref))
 }
@@ -1,13 +1,17 @@
 use pdf_writer::types::MaskType;
 use pdf_writer::{Chunk, Content, Filter, Finish, Ref};
 use usvg::tiny_skia_path::PathSegment;
-use usvg::{ClipPath, FillRule, Group, Node, Transform, Visibility};
+use usvg::{ClipPath, FillRule, Group, Node, NonZeroRect, Transform, Visibility};
 
 use super::group;
 use super::path::draw_path;
 use crate::util::context::Context;
-use crate::util::helper::{bbox_to_non_zero_rect, NameExt, RectExt, TransformExt};
+use crate::util::helper::{
+    bbox_to_non_zero_rect, intersect_rects, NameExt, RectExt, TransformExt,
+};
 use crate::util::resources::ResourceContainer;
+use crate::util::tags::{self, StreamTags};
+use crate::Result;
 
 /// Render a clip path into a content stream.
 pub fn render(
@@ -17,7 +21,7 @@ pub fn render(
     content: &mut Content,
     ctx: &mut Context,
     rc: &mut ResourceContainer,
-) {
+) -> Result<()> {
     // Unfortunately, clip paths are a bit tricky to deal with, the reason being that clip paths in
     // SVGs can be much more complex than in PDF. In SVG, clip paths can have transforms, as well as
     // nested clip paths. The objects inside of the clip path can have transforms as well, making it
@@ -54,10 +58,35 @@ pub fn render(
             clip_rules.first().copied().unwrap_or(FillRule::NonZero),
         );
     } else {
-        let clip_path_ref = create_complex_clip_path(group, clip_path, chunk, ctx);
+        let clip_path_ref = create_complex_clip_path(group, clip_path, chunk, ctx)?;
         let clip_path_name = rc.add_graphics_state(clip_path_ref);
         content.set_parameters(clip_path_name.to_pdf_name());
     }
+
+    Ok(())
+}
+
+/// Restrict a visible area (see [`Context::visible_area`]) to the bounding box of the
+/// clip path of a group. If the area can't be restricted, it is returned unchanged.
+/// Returns `None` if the clip path doesn't overlap with the area, in which case
+/// nothing inside of it can be visible.
+pub fn restrict_visible_area(
+    group: &Group,
+    clip_path: &ClipPath,
+    area: Option<NonZeroRect>,
+) -> Option<Option<NonZeroRect>> {
+    let Some(clip_bbox) =
+        clip_path.root().bounding_box().to_non_zero_rect().and_then(|bbox| {
+            bbox.transform(group.abs_transform().pre_concat(clip_path.transform()))
+        })
+    else {
+        return Some(area);
+    };
+
+    match area {
+        Some(area) => intersect_rects(area, clip_bbox).map(Some),
+        None => Some(Some(clip_bbox)),
+    }
 }
 
 fn is_simple_clip_path(group: &Group) -> bool {
@@ -126,32 +155,29 @@ fn extend_segments_from_group(
 ) {
     for child in group.children() {
         match child {
-            Node::Path(ref path) => {
-                if path.visibility() != Visibility::Hidden {
-                    path.data().segments().for_each(|segment| match segment {
-                        PathSegment::MoveTo(mut p) => {
-                            transform.map_point(&mut p);
-                            segments.push(PathSegment::MoveTo(p));
-                        }
-                        PathSegment::LineTo(mut p) => {
-                            transform.map_point(&mut p);
-                            segments.push(PathSegment::LineTo(p));
-                        }
-                        PathSegment::QuadTo(p1, p2) => {
-                            let mut points = [p1, p2];
-                            transform.map_points(&mut points);
-                            segments.push(PathSegment::QuadTo(points[0], points[1]));
-                        }
-                        PathSegment::CubicTo(p1, p2, p3) => {
-                            let mut points = [p1, p2, p3];
-                            transform.map_points(&mut points);
-                            segments.push(PathSegment::CubicTo(
-                                points[0], points[1], points[2],
-                            ));
-                        }
-                        PathSegment::Close => segments.push(PathSegment::Close),
-                    })
-                }
+            Node::Path(ref path) if path.visibility() != Visibility::Hidden => {
+                path.data().segments().for_each(|segment| match segment {
+                    PathSegment::MoveTo(mut p) => {
+                        transform.map_point(&mut p);
+                        segments.push(PathSegment::MoveTo(p));
+                    }
+                    PathSegment::LineTo(mut p) => {
+                        transform.map_point(&mut p);
+                        segments.push(PathSegment::LineTo(p));
+                    }
+                    PathSegment::QuadTo(p1, p2) => {
+                        let mut points = [p1, p2];
+                        transform.map_points(&mut points);
+                        segments.push(PathSegment::QuadTo(points[0], points[1]));
+                    }
+                    PathSegment::CubicTo(p1, p2, p3) => {
+                        let mut points = [p1, p2, p3];
+                        transform.map_points(&mut points);
+                        segments
+                            .push(PathSegment::CubicTo(points[0], points[1], points[2]));
+                    }
+                    PathSegment::Close => segments.push(PathSegment::Close),
+                })
             }
             Node::Group(ref group) => {
                 let group_transform = transform.pre_concat(group.transform());
@@ -173,30 +199,36 @@ fn create_complex_clip_path(
     clip_path: &ClipPath,
     chunk: &mut Chunk,
     ctx: &mut Context,
-) -> Ref {
+) -> Result<Ref> {
     let mut rc = ResourceContainer::new();
     let x_ref = ctx.alloc_ref();
+    // The content of clip paths can't be part of the structure tree.
+    rc.tags = ctx.options.tagged.then(|| StreamTags::new(Some(x_ref)));
 
     let mut content = Content::new();
     content.save_state();
 
     if let Some(clip_path) = clip_path.clip_path() {
-        render(parent, clip_path, chunk, &mut content, ctx, &mut rc);
+        render(parent, clip_path, chunk, &mut content, ctx, &mut rc)?;
     }
 
     content.transform(clip_path.transform().to_pdf_transform());
 
     let pdf_bbox = bbox_to_non_zero_rect(Some(parent.bounding_box())).to_pdf_rect();
 
-    group::render(
-        clip_path.root(),
-        chunk,
-        &mut content,
-        ctx,
-        Transform::default(),
-        None,
-        &mut rc,
-    );
+    tags::render_artifact(&mut content, &mut rc, |content, rc| {
+        ctx.with_root(None, None, |ctx| {
+            group::render(
+                clip_path.root(),
+                chunk,
+                content,
+                ctx,
+                Transform::default(),
+                None,
+                rc,
+            )
+        })
+    })?;
     content.restore_state();
 
     let content_stream = ctx.finish_content(content);
@@ -215,7 +247,7 @@ fn create_complex_clip_path(
         .isolated(false)
         .knockout(false)
         .color_space()
-        .icc_based(ctx.srgb_ref());
+        .icc_based(ctx.color_space_ref());
 
     x_object.bbox(pdf_bbox);
     x_object.finish();
@@ -224,5 +256,5 @@ fn create_complex_clip_path(
     let mut gs = chunk.ext_graphics(gs_ref);
     gs.soft_mask().subtype(MaskType::Alpha).group(x_ref);
 
-    gs_ref
+    Ok(gs_ref)
 }
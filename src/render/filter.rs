@@ -1,10 +1,17 @@
-use crate::render::image;
+use super::Render;
+use crate::render::{clip_path, image, path};
 use crate::util::context::Context;
+use crate::util::helper::{
+    clip_to_rect, intersect_rects, ColorExt, NameExt, RectExt, TransformExt,
+};
 use crate::util::resources::ResourceContainer;
-use pdf_writer::{Chunk, Content};
-use std::sync::Arc;
+use crate::util::tags::{self, StreamTags};
+use crate::Result;
+use pdf_writer::types::{ColorSpaceOperand, MaskType};
+use pdf_writer::{Chunk, Content, Filter as PdfFilter, Finish, Ref};
 use tiny_skia::{Size, Transform};
-use usvg::{AspectRatio, Group, ImageKind, Node, ViewBox, Visibility};
+use usvg::filter::{Filter, Flood, Input, Kind};
+use usvg::{Group, Node, NonZeroRect, Opacity};
 
 /// Render a group with filters as an image.
 pub fn render(
@@ -13,29 +20,76 @@ pub fn render(
     content: &mut Content,
     ctx: &mut Context,
     rc: &mut ResourceContainer,
-) -> Option<()> {
-    // TODO: Add a check so that huge regions don't crash svg2pdf (see huge-region.svg test case)
-    let layer_bbox = group.layer_bounding_box().transform(group.transform())?;
-    let pixmap_size = Size::from_wh(
-        layer_bbox.width() * ctx.options.raster_scale,
-        layer_bbox.height() * ctx.options.raster_scale,
-    )?;
-
-    let mut pixmap = tiny_skia::Pixmap::new(
-        pixmap_size.width().round() as u32,
-        pixmap_size.height().round() as u32,
-    )?;
-
-    let initial_transform =
-        Transform::from_scale(ctx.options.raster_scale, ctx.options.raster_scale)
-            .pre_concat(Transform::from_translate(-layer_bbox.x(), -layer_bbox.y()))
-            // This one is a hack because resvg::render_node will take the absolute layer bbox into consideration
-            // and translate by -layer_bbox.x() and -layer_bbox.y(), but we don't want that, so we
-            // inverse it.
-            .pre_concat(Transform::from_translate(
-                group.abs_layer_bounding_box().x(),
-                group.abs_layer_bounding_box().y(),
-            ));
+) -> Result<()> {
+    // If the filter region is empty, there is nothing to render.
+    let Some(layer_bbox) = group.layer_bounding_box().transform(group.transform()) else {
+        return Ok(());
+    };
+
+    // Only the part of the filter region that can end up being visible needs to be
+    // rasterized. Since the visible area is in the coordinate system of the root, we
+    // first need to map it into the one of the parent of the group.
+    let mut visible_area = ctx.visible_area;
+    if let Some(clip_path) = group.clip_path() {
+        let Some(area) = clip_path::restrict_visible_area(group, clip_path, visible_area)
+        else {
+            return Ok(());
+        };
+        visible_area = area;
+    }
+    let parent_transform = group
+        .transform()
+        .invert()
+        .map(|ts| group.abs_transform().pre_concat(ts))
+        .and_then(|ts| ts.invert());
+    let region = match visible_area.zip(parent_transform) {
+        Some((area, ts)) => match area.transform(ts) {
+            Some(area) => {
+                let Some(region) = intersect_rects(layer_bbox, area) else {
+                    return Ok(());
+                };
+                region
+            }
+            None => layer_bbox,
+        },
+        None => layer_bbox,
+    };
+
+    let mut raster_scale = ctx.options.raster_scale;
+    // Always allow at least one pixel, otherwise the raster scale would drop to
+    // zero and the filter would silently disappear.
+    let max_area = ctx.options.max_pixmap_area.max(1) as f32;
+    let area = region.width() * region.height() * raster_scale * raster_scale;
+    if area > max_area {
+        raster_scale = (max_area / (region.width() * region.height())).sqrt();
+        log::warn!(
+            "Filter region is too large to be rasterized with the configured raster \
+             scale. Lowering it to {raster_scale}."
+        );
+    }
+
+    let Some(pixmap_size) =
+        Size::from_wh(region.width() * raster_scale, region.height() * raster_scale)
+    else {
+        return Ok(());
+    };
+
+    let Some(mut pixmap) = tiny_skia::Pixmap::new(
+        pixmap_size.width().round().max(1.0) as u32,
+        pixmap_size.height().round().max(1.0) as u32,
+    ) else {
+        return Ok(());
+    };
+
+    let initial_transform = Transform::from_scale(raster_scale, raster_scale)
+        .pre_concat(Transform::from_translate(-region.x(), -region.y()))
+        // This one is a hack because resvg::render_node will take the absolute layer bbox into consideration
+        // and translate by -layer_bbox.x() and -layer_bbox.y(), but we don't want that, so we
+        // inverse it.
+        .pre_concat(Transform::from_translate(
+            group.abs_layer_bounding_box().x(),
+            group.abs_layer_bounding_box().y(),
+        ));
 
     resvg::render_node(
         &Node::Group(Box::new(group.clone())),
@@ -43,17 +97,268 @@ pub fn render(
         &mut pixmap.as_mut(),
     );
 
-    let encoded_image = pixmap.encode_png().ok()?;
+    image::render_pixmap(&pixmap, region, chunk, content, ctx, rc)
+}
 
-    image::render(
-        Visibility::Visible,
-        &ImageKind::PNG(Arc::new(encoded_image)),
-        ViewBox { rect: layer_bbox, aspect: AspectRatio::default() },
-        chunk,
-        content,
-        ctx,
-        rc,
-    );
+/// Whether the filters of a group can be rendered as vector content with
+/// [`render_vector`] instead of being rasterized.
+pub fn is_vector(group: &Group) -> bool {
+    match group.filters() {
+        [filter] => vector_layers(filter).is_some(),
+        _ => false,
+    }
+}
+
+/// Render the children of a group with filters as vector content. Should only be
+/// called if [`is_vector`] returned true for the group.
+pub fn render_vector(
+    group: &Group,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    let [filter] = group.filters() else {
+        return Ok(());
+    };
+    let Some(layers) = vector_layers(filter) else {
+        return Ok(());
+    };
+
+    // The source graphic is written into an XObject once, so that it doesn't need to be
+    // duplicated if it is referenced by multiple primitives. In tagged documents, only
+    // its topmost copy is tagged, while all other ones are drawn as artifacts from an
+    // untagged XObject, so that its content doesn't appear in the structure twice.
+    let tagged_layer = match rc.tags {
+        Some(_) => layers
+            .iter()
+            .rposition(|l| matches!(l.kind, LayerKind::SourceGraphic)),
+        None => None,
+    };
+    let mut tagged_source = None;
+    let mut untagged_source = None;
+    let mut source = |tagged: bool, chunk: &mut Chunk, ctx: &mut Context| {
+        let cache = if tagged { &mut tagged_source } else { &mut untagged_source };
+        match *cache {
+            Some(x_ref) => Ok(x_ref),
+            None => Ok(*cache.insert(create_source(
+                group,
+                filter.rect(),
+                chunk,
+                ctx,
+                tagged,
+            )?)),
+        }
+    };
+
+    for (i, layer) in layers.into_iter().enumerate() {
+        content.save_state();
+        clip_to_rect(layer.clip, content);
+        content
+            .transform(Transform::from_translate(layer.dx, layer.dy).to_pdf_transform());
+
+        match layer.kind {
+            LayerKind::SourceGraphic if tagged_layer == Some(i) => {
+                let x_ref = source(true, chunk, ctx)?;
+                content.x_object(rc.add_x_object(x_ref).to_pdf_name());
+            }
+            LayerKind::SourceGraphic => {
+                let x_ref = source(false, chunk, ctx)?;
+                tags::render_artifact(content, rc, |content, rc| {
+                    content.x_object(rc.add_x_object(x_ref).to_pdf_name());
+                    Ok(())
+                })?;
+            }
+            LayerKind::SourceAlpha => {
+                // The alpha channel of the source graphic is black with the
+                // opacity of the source, which we get by using it as a soft mask.
+                let x_ref = source(false, chunk, ctx)?;
+                let gs_ref = ctx.alloc_ref();
+                let mut gs = chunk.ext_graphics(gs_ref);
+                gs.soft_mask().subtype(MaskType::Alpha).group(x_ref);
+                gs.finish();
+                content.set_parameters(rc.add_graphics_state(gs_ref).to_pdf_name());
+                tags::render_artifact(content, rc, |content, rc| {
+                    fill_rect(
+                        filter.rect(),
+                        [0.0; 3],
+                        Opacity::ONE,
+                        chunk,
+                        content,
+                        ctx,
+                        rc,
+                    );
+                    Ok(())
+                })?;
+            }
+            LayerKind::Flood(flood) => {
+                let color = flood.color().to_pdf_color();
+                tags::render_artifact(content, rc, |content, rc| {
+                    fill_rect(
+                        filter.rect(),
+                        color,
+                        flood.opacity(),
+                        chunk,
+                        content,
+                        ctx,
+                        rc,
+                    );
+                    Ok(())
+                })?;
+            }
+        }
+
+        content.restore_state();
+    }
+
+    Ok(())
+}
 
-    Some(())
+/// A layer of a filter result that can be drawn as vector content.
+#[derive(Clone)]
+struct Layer {
+    kind: LayerKind,
+    /// The offset by which the layer is translated.
+    dx: f32,
+    dy: f32,
+    /// The region the layer is clipped to. Unlike the content of the layer, it is
+    /// already translated by the offset.
+    clip: NonZeroRect,
+}
+
+#[derive(Clone)]
+enum LayerKind {
+    SourceGraphic,
+    SourceAlpha,
+    Flood(Flood),
+}
+
+impl Layer {
+    fn offset(mut self, dx: f32, dy: f32) -> Option<Self> {
+        self.dx += dx;
+        self.dy += dy;
+        self.clip = self.clip.transform(Transform::from_translate(dx, dy))?;
+        Some(self)
+    }
+
+    fn clip(mut self, rect: NonZeroRect) -> Option<Self> {
+        self.clip = intersect_rects(self.clip, rect)?;
+        Some(self)
+    }
+}
+
+/// Express the result of a filter as a list of layers that are painted on top of each
+/// other. This is possible for filters made up only of `feFlood`, `feOffset` and
+/// `feMerge` primitives. For all other filters, `None` is returned.
+///
+/// Note that `feMerge` always composites in sRGB here, which only makes a difference
+/// for translucent layers that overlap.
+fn vector_layers(filter: &Filter) -> Option<Vec<Layer>> {
+    let source = |kind| Layer { kind, dx: 0.0, dy: 0.0, clip: filter.rect() };
+    let mut results: Vec<(&str, Vec<Layer>)> = vec![];
+
+    let input = |input: &Input, results: &[(&str, Vec<Layer>)]| match input {
+        Input::SourceGraphic => Some(vec![source(LayerKind::SourceGraphic)]),
+        Input::SourceAlpha => Some(vec![source(LayerKind::SourceAlpha)]),
+        Input::Reference(name) => results
+            .iter()
+            .rev()
+            .find(|(result, _)| result == name)
+            .map(|(_, layers)| layers.clone()),
+    };
+
+    for primitive in filter.primitives() {
+        let layers = match primitive.kind() {
+            Kind::Flood(flood) => vec![source(LayerKind::Flood(*flood))],
+            Kind::Offset(offset) => input(offset.input(), &results)?
+                .into_iter()
+                .filter_map(|layer| layer.offset(offset.dx(), offset.dy()))
+                .collect(),
+            Kind::Merge(merge) => merge
+                .inputs()
+                .iter()
+                .map(|i| input(i, &results))
+                .collect::<Option<Vec<_>>>()?
+                .concat(),
+            _ => return None,
+        };
+
+        let layers = layers
+            .into_iter()
+            .filter_map(|layer| layer.clip(primitive.rect())?.clip(filter.rect()))
+            .collect();
+        results.push((primitive.result(), layers));
+    }
+
+    results.pop().map(|(_, layers)| layers)
+}
+
+/// Write the children of a group into an XObject, clipped to the filter region.
+/// Its content is tagged if `tagged` is true.
+fn create_source(
+    group: &Group,
+    rect: NonZeroRect,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+    tagged: bool,
+) -> Result<Ref> {
+    let x_ref = ctx.alloc_ref();
+    let mut rc = ResourceContainer::new();
+    if tagged {
+        rc.tags = Some(StreamTags::new(Some(x_ref)));
+    }
+
+    let mut content = Content::new();
+
+    // See the comment in `group::render` on why we don't pass on the accumulated
+    // transform here.
+    for child in group.children() {
+        child.render(chunk, &mut content, ctx, Transform::default(), &mut rc)?;
+    }
+
+    let content_stream = ctx.finish_content(content);
+    let struct_parents = rc.tags.take().and_then(|tags| ctx.tags.finish_stream(tags));
+
+    let mut x_object = chunk.form_xobject(x_ref, &content_stream);
+    rc.finish(&mut x_object.resources());
+
+    if let Some(key) = struct_parents {
+        x_object.struct_parents(key);
+    }
+
+    if ctx.options.compress {
+        x_object.filter(PdfFilter::FlateDecode);
+    }
+
+    x_object
+        .group()
+        .transparency()
+        .isolated(true)
+        .knockout(false)
+        .color_space()
+        .icc_based(ctx.color_space_ref());
+
+    x_object.bbox(rect.to_pdf_rect());
+    x_object.finish();
+
+    Ok(x_ref)
+}
+
+/// Fill a rectangle with a solid color.
+fn fill_rect(
+    rect: NonZeroRect,
+    color: [f32; 3],
+    opacity: Opacity,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) {
+    path::set_opacity_gs(chunk, content, ctx, None, Some(opacity), rc);
+    let color_space_name = rc.add_color_space(ctx.color_space_ref());
+    content
+        .set_fill_color_space(ColorSpaceOperand::Named(color_space_name.to_pdf_name()));
+    content.set_fill_color(ctx.convert_color(color));
+    content.rect(rect.x(), rect.y(), rect.width(), rect.height());
+    content.fill_nonzero();
 }
@@ -1,6 +1,7 @@
 use pdf_writer::types::{FunctionShadingType, MaskType};
 use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref};
-use usvg::{Paint, Rect, Transform};
+use usvg::tiny_skia_path::Point;
+use usvg::{Paint, Rect, SpreadMethod, Transform};
 
 use crate::util::context::Context;
 use crate::util::helper::{
@@ -8,8 +9,14 @@ use crate::util::helper::{
 };
 use crate::util::resources::ResourceContainer;
 
+/// The maximum number of periods that a reflected or repeated gradient is tiled
+/// over. Beyond them, the gradient is padded instead, so that a tiny gradient
+/// vector on a huge shape does not produce a huge stitching function. Each period
+/// takes up about 20 bytes in it.
+const MAX_SPREAD_PERIODS: f32 = 8192.0;
+
 /// An alternative representation of a usvg::Stop that allows us to store
-/// both, RGB gradients and grayscale gradients.
+/// RGB, CMYK and grayscale gradients.
 #[derive(Copy, Clone)]
 pub struct Stop<const COUNT: usize> {
     pub color: [f32; COUNT],
@@ -21,6 +28,7 @@ struct GradientProperties {
     shading_type: FunctionShadingType,
     stops: Vec<usvg::Stop>,
     transform: Transform,
+    spread_method: SpreadMethod,
 }
 
 impl GradientProperties {
@@ -31,16 +39,137 @@ impl GradientProperties {
                 shading_type: FunctionShadingType::Axial,
                 stops: Vec::from(l.stops()),
                 transform: l.transform(),
+                spread_method: l.spread_method(),
             }),
             Paint::RadialGradient(r) => Some(Self {
                 coords: vec![r.fx(), r.fy(), 0.0, r.cx(), r.cy(), r.r().get()],
                 shading_type: FunctionShadingType::Radial,
                 stops: Vec::from(r.stops()),
                 transform: r.transform(),
+                spread_method: r.spread_method(),
             }),
             _ => None,
         }
     }
+
+    /// Calculate the range of the gradient parameter `t` that is needed to cover
+    /// the whole bounding box (given in user space), rounded outwards to whole
+    /// periods of the gradient and limited to [`MAX_SPREAD_PERIODS`] periods.
+    fn parameter_range(&self, bbox: Rect) -> (f32, f32) {
+        let Some(inverse) = self.transform.invert() else { return (0.0, 1.0) };
+
+        let mut corners = [
+            Point::from_xy(bbox.left(), bbox.top()),
+            Point::from_xy(bbox.right(), bbox.top()),
+            Point::from_xy(bbox.left(), bbox.bottom()),
+            Point::from_xy(bbox.right(), bbox.bottom()),
+        ];
+        inverse.map_points(&mut corners);
+
+        let parameters = corners.iter().map(|p| match self.shading_type {
+            FunctionShadingType::Radial => self.radial_parameter(*p),
+            _ => self.axial_parameter(*p),
+        });
+
+        let (min, max) =
+            parameters.fold((0.0f32, 1.0f32), |(min, max), t| (min.min(t), max.max(t)));
+
+        // Radial gradients are not defined for negative parameters, since
+        // the radius of the circles would become negative.
+        let (min, max) = if self.shading_type == FunctionShadingType::Radial {
+            (0.0, max.ceil())
+        } else {
+            (min.floor(), max.ceil())
+        };
+
+        if max - min <= MAX_SPREAD_PERIODS {
+            return (min, max);
+        }
+
+        log::warn!(
+            "Gradient would need to be repeated {} times to cover its shape. Padding \
+             it after {MAX_SPREAD_PERIODS} repetitions.",
+            max - min
+        );
+
+        if self.shading_type == FunctionShadingType::Radial {
+            (0.0, MAX_SPREAD_PERIODS)
+        } else {
+            // Keep the periods around the gradient vector, but use all of them if
+            // the shape only extends far beyond it on one side.
+            let start = min.max(-MAX_SPREAD_PERIODS / 2.0);
+            let end = max.min(start + MAX_SPREAD_PERIODS);
+            (min.max(end - MAX_SPREAD_PERIODS), end)
+        }
+    }
+
+    /// The gradient parameter of a point for a linear gradient, i.e. the position of
+    /// the projection of the point onto the gradient vector.
+    fn axial_parameter(&self, p: Point) -> f32 {
+        let [x1, y1, x2, y2] = self.coords[..] else { return 0.0 };
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length = dx * dx + dy * dy;
+
+        if length == 0.0 {
+            return 0.0;
+        }
+
+        ((p.x - x1) * dx + (p.y - y1) * dy) / length
+    }
+
+    /// The gradient parameter of a point for a radial gradient, i.e. the largest `t`
+    /// for which the point lies on the circle with center `f + t * (c - f)` and
+    /// radius `t * r`.
+    fn radial_parameter(&self, p: Point) -> f32 {
+        let [fx, fy, _, cx, cy, r] = self.coords[..] else { return 1.0 };
+        let (ex, ey) = (cx - fx, cy - fy);
+        let (qx, qy) = (p.x - fx, p.y - fy);
+
+        let a = ex * ex + ey * ey - r * r;
+        let b = qx * ex + qy * ey;
+        let c = qx * qx + qy * qy;
+
+        let t = if a == 0.0 {
+            c / (2.0 * b)
+        } else {
+            let discriminant = b * b - a * c;
+            if discriminant < 0.0 {
+                return 1.0;
+            }
+
+            let sqrt = discriminant.sqrt();
+            ((b + sqrt) / a).max((b - sqrt) / a)
+        };
+
+        if t.is_finite() {
+            t
+        } else {
+            1.0
+        }
+    }
+
+    /// The coordinates of the shading, extended so that they span the parameter
+    /// range from `t0` to `t1` instead of the range from 0 to 1.
+    fn extended_coords(&self, t0: f32, t1: f32) -> Vec<f32> {
+        match self.coords[..] {
+            [x1, y1, x2, y2] => {
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                vec![x1 + t0 * dx, y1 + t0 * dy, x1 + t1 * dx, y1 + t1 * dy]
+            }
+            [fx, fy, _, cx, cy, r] => {
+                let (ex, ey) = (cx - fx, cy - fy);
+                vec![
+                    fx + t0 * ex,
+                    fy + t0 * ey,
+                    t0 * r,
+                    fx + t1 * ex,
+                    fy + t1 * ey,
+                    t1 * r,
+                ]
+            }
+            _ => self.coords.clone(),
+        }
+    }
 }
 
 /// Turn a (gradient) paint into a shading pattern object. Stop opacities will be ignored and
@@ -51,9 +180,10 @@ pub fn create_shading_pattern(
     chunk: &mut Chunk,
     ctx: &mut Context,
     accumulated_transform: &Transform,
+    bbox: Rect,
 ) -> Ref {
     let properties = GradientProperties::try_from_paint(paint).unwrap();
-    shading_pattern(&properties, chunk, ctx, accumulated_transform)
+    shading_pattern(&properties, chunk, ctx, accumulated_transform, bbox)
 }
 
 /// Return a soft mask that will render the stop opacities of a gradient into a gray scale
@@ -78,12 +208,13 @@ fn shading_pattern(
     chunk: &mut Chunk,
     ctx: &mut Context,
     accumulated_transform: &Transform,
+    bbox: Rect,
 ) -> Ref {
     let pattern_ref = ctx.alloc_ref();
 
     let matrix = accumulated_transform.pre_concat(properties.transform);
 
-    let shading_ref = shading_function(properties, chunk, ctx, false);
+    let shading_ref = shading_function(properties, chunk, ctx, bbox, false);
     let mut shading_pattern = chunk.shading_pattern(pattern_ref);
     shading_pattern.pair(Name(b"Shading"), shading_ref);
     shading_pattern.matrix(matrix.to_pdf_transform());
@@ -100,7 +231,7 @@ fn shading_soft_mask(
 ) -> Ref {
     let mut rc = ResourceContainer::new();
     let x_object_id = ctx.alloc_ref();
-    let shading_ref = shading_function(properties, chunk, ctx, true);
+    let shading_ref = shading_function(properties, chunk, ctx, bbox, true);
     let shading_name = rc.add_shading(shading_ref);
     let bbox = bbox_to_non_zero_rect(Some(bbox)).to_pdf_rect();
 
@@ -143,26 +274,85 @@ fn shading_function(
     properties: &GradientProperties,
     chunk: &mut Chunk,
     ctx: &mut Context,
+    bbox: Rect,
     use_opacities: bool,
 ) -> Ref {
     let shading_ref = ctx.alloc_ref();
     let function_ref = function(&properties.stops, chunk, ctx, use_opacities);
 
+    // PDF only supports padding natively, so for the other spread methods, we extend the
+    // shading so that it covers the whole bounding box and tile the function over the
+    // extended domain.
+    let (coords, domain, function_ref) = match properties.spread_method {
+        SpreadMethod::Pad => (properties.coords.clone(), [0.0, 1.0], function_ref),
+        spread_method => {
+            let (t0, t1) = properties.parameter_range(bbox);
+            let count = match (use_opacities, &ctx.cmyk) {
+                (true, _) => 1,
+                (false, Some(_)) => 4,
+                (false, None) => 3,
+            };
+            let function_ref =
+                spread_function(function_ref, t0, t1, spread_method, count, chunk, ctx);
+            (properties.extended_coords(t0, t1), [t0, t1], function_ref)
+        }
+    };
+
     let mut shading = chunk.function_shading(shading_ref);
     shading.shading_type(properties.shading_type);
     if use_opacities {
         shading.color_space().icc_based(ctx.sgray_ref());
     } else {
-        shading.color_space().icc_based(ctx.srgb_ref());
+        shading.color_space().icc_based(ctx.color_space_ref());
     }
 
     shading.function(function_ref);
-    shading.coords(properties.coords.iter().copied());
+    shading.coords(coords);
+    shading.insert(Name(b"Domain")).array().items(domain);
     shading.extend([true, true]);
     shading.finish();
     shading_ref
 }
 
+/// Tile a function with a domain from 0 to 1 over the domain from `t0` to `t1` (which
+/// need to be whole numbers), mirroring every other period in case of a reflected gradient.
+fn spread_function(
+    function_ref: Ref,
+    t0: f32,
+    t1: f32,
+    spread_method: SpreadMethod,
+    count: usize,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+) -> Ref {
+    let reference = ctx.alloc_ref();
+    let periods = (t1 - t0) as i32;
+
+    let mut bounds = vec![];
+    let mut encode = vec![];
+
+    for i in 0..periods {
+        let start = t0 as i32 + i;
+        if i > 0 {
+            bounds.push(start as f32);
+        }
+
+        if spread_method == SpreadMethod::Reflect && start.rem_euclid(2) == 1 {
+            encode.extend([1.0, 0.0]);
+        } else {
+            encode.extend([0.0, 1.0]);
+        }
+    }
+
+    let mut stitching_function = chunk.stitching_function(reference);
+    stitching_function.domain([t0, t1]);
+    stitching_function.range(get_function_range(count));
+    stitching_function.functions(vec![function_ref; periods as usize]);
+    stitching_function.bounds(bounds);
+    stitching_function.encode(encode);
+    reference
+}
+
 fn function(
     stops: &[usvg::Stop],
     chunk: &mut Chunk,
@@ -198,6 +388,17 @@ fn function(
         let stops =
             pad_stops(stops.iter().map(|s| s.opacity_stops()).collect::<Vec<Stop<1>>>());
         select_function(&stops, chunk, ctx)
+    } else if let Some(cmyk) = &ctx.cmyk {
+        let stops = pad_stops(
+            stops
+                .iter()
+                .map(|s| {
+                    let Stop { color, offset } = s.color_stops();
+                    Stop { color: cmyk.convert(color), offset }
+                })
+                .collect::<Vec<Stop<4>>>(),
+        );
+        select_function(&stops, chunk, ctx)
     } else {
         let stops =
             pad_stops(stops.iter().map(|s| s.color_stops()).collect::<Vec<Stop<3>>>());
@@ -1,13 +1,15 @@
 use pdf_writer::{Chunk, Content, Filter, Finish, Ref};
 use std::ops::Mul;
-use usvg::{Opacity, Transform};
+use usvg::{NonZeroRect, Opacity, Transform};
 
-#[cfg(feature = "filters")]
-use super::filter;
 use super::{clip_path, mask, Render};
 use crate::util::context::Context;
 use crate::util::helper::{BlendModeExt, GroupExt, NameExt, RectExt, TransformExt};
 use crate::util::resources::ResourceContainer;
+use crate::util::tags::StreamTags;
+use crate::Result;
+#[cfg(feature = "filters")]
+use {super::filter, crate::util::tags};
 
 /// Render a group into a content stream.
 pub fn render(
@@ -18,11 +20,13 @@ pub fn render(
     accumulated_transform: Transform,
     initial_opacity: Option<Opacity>,
     rc: &mut ResourceContainer,
-) {
+) -> Result<()> {
     #[cfg(feature = "filters")]
-    if !group.filters().is_empty() {
-        filter::render(group, chunk, content, ctx, rc);
-        return;
+    if !group.filters().is_empty() && !filter::is_vector(group) {
+        // Rasterized groups can't be tagged any further.
+        return tags::render_artifact(content, rc, |content, rc| {
+            filter::render(group, chunk, content, ctx, rc)
+        });
     }
 
     #[cfg(not(feature = "filters"))]
@@ -35,6 +39,15 @@ pub fn render(
     let initial_opacity = initial_opacity.unwrap_or(Opacity::ONE);
 
     if group.is_isolated() || initial_opacity.get() != 1.0 {
+        // If the group has no area or its transform cannot be inverted, nothing
+        // of it would be visible anyway.
+        let (Some(bbox), Some(inverse_transform)) = (
+            group.layer_bounding_box().transform(group.transform()),
+            group.transform().invert(),
+        ) else {
+            return Ok(());
+        };
+
         content.save_state();
         let gs_ref = ctx.alloc_ref();
         let mut gs = chunk.ext_graphics(gs_ref);
@@ -51,48 +64,58 @@ pub fn render(
         // hack of setting and then reversing the transform.
         if let Some(mask) = group.mask() {
             content.transform(group.transform().to_pdf_transform());
-            mask::render(group, mask, chunk, content, ctx, rc);
-            content.transform(group.transform().invert().unwrap().to_pdf_transform());
+            mask::render(group, mask, chunk, content, ctx, rc)?;
+            content.transform(inverse_transform.to_pdf_transform());
         }
 
         // We don't need to pass the accumulated transform here because if a pattern appears in a
         // XObject, it will be mapped to the coordinate space of where the XObject was invoked, meaning
         // that it will also be affected by the transforms in the content stream. If we passed on the
         // accumulated transform, they would be applied twice.
-        let x_ref = create_x_object(group, chunk, ctx, Transform::default());
+        let tagged = rc.tags.is_some();
+        let x_ref =
+            create_x_object(group, bbox, chunk, ctx, Transform::default(), tagged)?;
         let x_name = rc.add_x_object(x_ref);
         content.x_object(x_name.to_pdf_name());
         content.restore_state();
     } else {
-        create_to_stream(group, chunk, content, ctx, accumulated_transform, rc);
+        create_to_stream(group, chunk, content, ctx, accumulated_transform, rc)?;
     }
+
+    Ok(())
 }
 
-/// Turn a group into an XObject.
+/// Turn a group into an XObject, whose content is tagged if `tagged` is true.
 fn create_x_object(
     group: &usvg::Group,
+    bbox: NonZeroRect,
     chunk: &mut Chunk,
     ctx: &mut Context,
     accumulated_transform: Transform,
-) -> Ref {
+    tagged: bool,
+) -> Result<Ref> {
     let x_ref = ctx.alloc_ref();
     let mut rc = ResourceContainer::new();
+    if tagged {
+        rc.tags = Some(StreamTags::new(Some(x_ref)));
+    }
 
-    let pdf_bbox = group
-        .layer_bounding_box()
-        .transform(group.transform())
-        .unwrap()
-        .to_pdf_rect();
+    let pdf_bbox = bbox.to_pdf_rect();
 
     let mut content = Content::new();
 
-    create_to_stream(group, chunk, &mut content, ctx, accumulated_transform, &mut rc);
+    create_to_stream(group, chunk, &mut content, ctx, accumulated_transform, &mut rc)?;
 
     let content_stream = ctx.finish_content(content);
+    let struct_parents = rc.tags.take().and_then(|tags| ctx.tags.finish_stream(tags));
 
     let mut x_object = chunk.form_xobject(x_ref, &content_stream);
     rc.finish(&mut x_object.resources());
 
+    if let Some(key) = struct_parents {
+        x_object.struct_parents(key);
+    }
+
     if ctx.options.compress {
         x_object.filter(Filter::FlateDecode);
     }
@@ -103,12 +126,12 @@ fn create_x_object(
         .isolated(group.is_isolated())
         .knockout(false)
         .color_space()
-        .icc_based(ctx.srgb_ref());
+        .icc_based(ctx.color_space_ref());
 
     x_object.bbox(pdf_bbox);
     x_object.finish();
 
-    x_ref
+    Ok(x_ref)
 }
 
 /// Write a group into a content stream. Opacities will be ignored. If opacities are needed,
@@ -120,18 +143,52 @@ fn create_to_stream(
     ctx: &mut Context,
     accumulated_transform: Transform,
     rc: &mut ResourceContainer,
-) {
+) -> Result<()> {
+    let mut visible_area = ctx.visible_area;
+    if let Some(clip_path) = group.clip_path() {
+        // If the clip path doesn't overlap with the visible area, nothing inside of it
+        // can be visible.
+        let Some(area) = clip_path::restrict_visible_area(group, clip_path, visible_area)
+        else {
+            return Ok(());
+        };
+        visible_area = area;
+    }
+
     content.save_state();
     content.transform(group.transform().to_pdf_transform());
     let accumulated_transform = accumulated_transform.pre_concat(group.transform());
 
-    if let Some(clip_path) = &group.clip_path() {
-        clip_path::render(group, clip_path, chunk, content, ctx, rc);
+    if let Some(clip_path) = group.clip_path() {
+        clip_path::render(group, clip_path, chunk, content, ctx, rc)?;
+    }
+
+    ctx.with_visible_area(visible_area, |ctx| {
+        render_children(group, chunk, content, ctx, accumulated_transform, rc)
+    })?;
+
+    content.restore_state();
+
+    Ok(())
+}
+
+/// Write the children of a group into a content stream.
+fn render_children(
+    group: &usvg::Group,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    accumulated_transform: Transform,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    #[cfg(feature = "filters")]
+    if !group.filters().is_empty() {
+        return filter::render_vector(group, chunk, content, ctx, rc);
     }
 
     for child in group.children() {
-        child.render(chunk, content, ctx, accumulated_transform, rc);
+        child.render(chunk, content, ctx, accumulated_transform, rc)?;
     }
 
-    content.restore_state();
+    Ok(())
 }
@@ -1,84 +1,356 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
 use std::rc::Rc;
 
-use image::{ColorType, DynamicImage, ImageFormat, Luma, Rgb, Rgba};
+use image::codecs::jpeg::{JpegDecoder, JpegEncoder};
+use image::codecs::png::PngDecoder;
+use image::imageops::FilterType;
+use image::{
+    ColorType, DynamicImage, ImageDecoder, ImageFormat, ImageResult, Luma, Rgb, Rgba,
+};
 use miniz_oxide::deflate::{compress_to_vec_zlib, CompressionLevel};
-use pdf_writer::{Chunk, Content, Filter, Finish};
-use usvg::{ImageKind, Size, Transform, Tree, ViewBox, Visibility};
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref, Str};
+#[cfg(feature = "filters")]
+use usvg::{AspectRatio, NonZeroRect};
+use usvg::{
+    Group, Image, ImageKind, ImageRendering, Node, Size, Transform, Tree, ViewBox,
+    Visibility, WriteOptions,
+};
 
 use crate::render::tree_to_xobject;
 use crate::util::context::Context;
 use crate::util::helper;
-use crate::util::helper::{image_rect, NameExt, TransformExt};
+use crate::util::helper::{hash128, image_rect, NameExt, TransformExt};
 use crate::util::resources::ResourceContainer;
+use crate::{ConversionError, ConversionOptions, ResamplingFilter, Result};
+
+/// The JPEG quality with which resampled JPEG images are encoded again.
+const RESAMPLED_JPEG_QUALITY: u8 = 90;
 
 /// Render an image into a content stream.
 pub fn render(
-    visibility: Visibility,
-    kind: &ImageKind,
-    view_box: ViewBox,
+    image: &Image,
     chunk: &mut Chunk,
     content: &mut Content,
     ctx: &mut Context,
     rc: &mut ResourceContainer,
-) {
-    if visibility != Visibility::Visible {
-        return;
-    }
-
-    // Will return the name of the image (in the Resources dictionary) and the dimensions of the
-    // actual image (i.e. the actual image size, not the size in the PDF, which will always be 1x1
-    // because that's how ImageXObjects are scaled by default.
-    let (image_name, image_size) = match kind {
-        ImageKind::JPEG(content) => {
-            let dynamic_image =
-                image::load_from_memory_with_format(content, ImageFormat::Jpeg).unwrap();
-            // JPEGs don't support alphas, so no extra processing is required.
-            create_raster_image(
-                chunk,
-                ctx,
-                content,
-                Filter::DctDecode,
-                &dynamic_image,
-                None,
-                rc,
-            )
+) -> Result<()> {
+    if image.visibility() != Visibility::Visible {
+        return Ok(());
+    }
+
+    let kind = image.kind();
+    let view_box = image.view_box();
+    let pixelated = image.rendering_mode() == ImageRendering::OptimizeSpeed;
+
+    // PDF/A forbids enabling interpolation, so we leave it up to the viewer there.
+    let interpolate =
+        if pixelated { Some(false) } else { (!ctx.options.pdfa).then_some(true) };
+
+    // Depending on the resolution they end up with on the page, images are resampled.
+    let resampled_size = ctx.root_transform.and_then(|root| {
+        let transform = root.pre_concat(image.abs_transform());
+        resampled_size(kind, view_box, transform, pixelated, &ctx.options)
+    });
+
+    // Identical images, e.g. ones that are referenced multiple times with `<use>`, are
+    // only written once.
+    let hash = hash128(&(hash_image(kind, ctx), resampled_size, interpolate));
+    let (image_ref, image_size) = match ctx.images.get(&hash) {
+        Some(&image) => image,
+        None => {
+            let image = match resampled_size {
+                Some(size) => create_resampled_image(
+                    kind,
+                    size,
+                    pixelated,
+                    interpolate,
+                    chunk,
+                    ctx,
+                )?,
+                None => create_image(kind, interpolate, chunk, ctx)?,
+            };
+            ctx.images.insert(hash, image);
+            image
         }
+    };
+
+    let image_name = rc.add_x_object(image_ref);
+    draw_image(&image_name, image_size, view_box, content);
+
+    Ok(())
+}
+
+/// Write an image into an XObject. Returns the reference to it and the dimensions of
+/// the actual image (i.e. the actual image size, not the size in the PDF, which will
+/// always be 1x1 because that's how ImageXObjects are scaled by default).
+pub fn create_image(
+    kind: &ImageKind,
+    interpolate: Option<bool>,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+) -> Result<(Ref, Size)> {
+    Ok(match kind {
+        // JPEGs don't support alphas, so they can be embedded as is.
+        ImageKind::JPEG(content) => create_jpeg_image(chunk, ctx, content, interpolate)?,
         ImageKind::PNG(content) => {
-            let dynamic_image =
-                image::load_from_memory_with_format(content, ImageFormat::Png).unwrap();
-            // Alpha channels need to be written separately as a soft mask, hence the extra processing
-            // step.
-            let (samples, filter, alpha_mask) = handle_transparent_image(&dynamic_image);
-            create_raster_image(
-                chunk,
-                ctx,
-                &samples,
-                filter,
-                &dynamic_image,
-                alpha_mask.as_deref(),
-                rc,
-            )
+            // Opaque PNGs can be embedded without decoding them.
+            let (image, icc) = match PngInfo::parse(content).and_then(PngInfo::into_image)
+            {
+                Some((image, icc)) => (image, icc),
+                None => {
+                    let (dynamic_image, icc) = decode(content, ImageFormat::Png)?;
+                    // Alpha channels need to be written separately as a soft mask, hence
+                    // the extra processing step.
+                    (handle_transparent_image(&dynamic_image)?, icc)
+                }
+            };
+            create_raster_image(chunk, ctx, &image, icc.as_deref(), interpolate)?
         }
         ImageKind::GIF(content) => {
-            let dynamic_image =
-                image::load_from_memory_with_format(content, ImageFormat::Gif).unwrap();
+            let (dynamic_image, icc) = decode(content, ImageFormat::Gif)?;
             // Alpha channels need to be written separately as a soft mask, hence the extra processing
             // step.
-            let (samples, filter, alpha_mask) = handle_transparent_image(&dynamic_image);
-            create_raster_image(
-                chunk,
-                ctx,
-                &samples,
-                filter,
-                &dynamic_image,
-                alpha_mask.as_deref(),
-                rc,
-            )
+            let image = handle_transparent_image(&dynamic_image)?;
+            create_raster_image(chunk, ctx, &image, icc.as_deref(), interpolate)?
         }
         // SVGs just get rendered recursively.
-        ImageKind::SVG(tree) => create_svg_image(tree, chunk, ctx, rc),
+        ImageKind::SVG(tree) => create_svg_image(tree, chunk, ctx)?,
+    })
+}
+
+/// Calculate the size in pixels an image needs to be resampled to, so that its
+/// resolution matches the limits of the conversion options when it is drawn into the
+/// view box with the given transform into points. Returns `None` if the image doesn't
+/// need to be resampled.
+fn resampled_size(
+    kind: &ImageKind,
+    view_box: ViewBox,
+    transform: Transform,
+    pixelated: bool,
+    options: &ConversionOptions,
+) -> Option<(u32, u32)> {
+    let min_dpi = options.pixelated_image_dpi.filter(|_| pixelated);
+    if options.max_image_dpi.is_none() && min_dpi.is_none() {
+        return None;
+    }
+
+    let (width, height) = match kind {
+        // CMYK JPEGs can't be resampled without losing their colors, since they are
+        // converted into RGB when decoding them.
+        ImageKind::JPEG(data) => JpegInfo::parse(data)
+            .filter(|info| matches!(info.components, 1 | 3))
+            .map(|info| (u32::from(info.width), u32::from(info.height)))?,
+        ImageKind::PNG(data) => image_dimensions(data, ImageFormat::Png)?,
+        ImageKind::GIF(data) => image_dimensions(data, ImageFormat::Gif)?,
+        ImageKind::SVG(_) => return None,
+    };
+
+    let size = Size::from_wh(width as f32, height as f32)?;
+    let rect = image_rect(&view_box, size);
+
+    // The lengths of the image's edges on the page, in inches.
+    let placed_width = rect.width() * transform.sx.hypot(transform.ky) / 72.0;
+    let placed_height = rect.height() * transform.kx.hypot(transform.sy) / 72.0;
+
+    let (mut new_width, mut new_height) = (width, height);
+
+    if let Some(min_dpi) = min_dpi {
+        // Only scale up by whole numbers, so that all pixels stay equally large. The
+        // maximum resolution is applied before rounding, so that the factor doesn't
+        // exceed it.
+        let factor = |length: u32, placed: f32| {
+            let factor = (placed * min_dpi / length as f32).ceil();
+            let max_factor = options.max_image_dpi.map_or(f32::INFINITY, |max_dpi| {
+                (placed * max_dpi / length as f32).floor()
+            });
+            factor.min(max_factor).max(1.0)
+        };
+        let mut factor_x = factor(width, placed_width);
+        let mut factor_y = factor(height, placed_height);
+
+        // Like rasterized effects, the upscaled image may not have more pixels than
+        // the maximum pixmap area. It is calculated with double precision, so that a
+        // factor that fits exactly isn't rounded down.
+        let area = f64::from(width) * f64::from(height) * f64::from(factor_x * factor_y);
+        let max_area = f64::from(options.max_pixmap_area.max(1));
+        if area > max_area {
+            let shrink = (max_area / area).sqrt();
+            factor_x = (f64::from(factor_x) * shrink).floor().max(1.0) as f32;
+            factor_y = (f64::from(factor_y) * shrink).floor().max(1.0) as f32;
+        }
+
+        new_width = (width as f32 * factor_x) as u32;
+        new_height = (height as f32 * factor_y) as u32;
+    }
+
+    if let Some(max_dpi) = options.max_image_dpi {
+        new_width = new_width.min((placed_width * max_dpi).ceil().max(1.0) as u32);
+        new_height = new_height.min((placed_height * max_dpi).ceil().max(1.0) as u32);
+    }
+
+    ((new_width, new_height) != (width, height)).then_some((new_width, new_height))
+}
+
+/// Read the dimensions of a raster image without decoding it.
+fn image_dimensions(data: &[u8], format: ImageFormat) -> Option<(u32, u32)> {
+    image::io::Reader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .ok()
+}
+
+/// Write an image that is resampled to the given size into an XObject. Returns the
+/// reference to it and the dimensions of the original image, so that it is placed in
+/// the same way as the original one.
+fn create_resampled_image(
+    kind: &ImageKind,
+    (width, height): (u32, u32),
+    pixelated: bool,
+    interpolate: Option<bool>,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+) -> Result<(Ref, Size)> {
+    let (data, format) = match kind {
+        ImageKind::JPEG(data) => (data, ImageFormat::Jpeg),
+        ImageKind::PNG(data) => (data, ImageFormat::Png),
+        ImageKind::GIF(data) => (data, ImageFormat::Gif),
+        ImageKind::SVG(tree) => return create_svg_image(tree, chunk, ctx),
+    };
+
+    let (dynamic_image, icc) = decode(data, format)?;
+    let original_size =
+        Size::from_wh(dynamic_image.width() as f32, dynamic_image.height() as f32)
+            .ok_or_else(|| {
+                ConversionError::InvalidImage("image has a size of zero".to_string())
+            })?;
+
+    // Pixelated images keep their hard edges.
+    let filter = if pixelated {
+        FilterType::Nearest
+    } else {
+        filter_type(ctx.options.image_resampling)
+    };
+    let resampled = dynamic_image.resize_exact(width, height, filter);
+    // Photos are better off staying JPEGs.
+    let image = match format {
+        ImageFormat::Jpeg if !pixelated => encode_jpeg(&resampled)?,
+        _ => handle_transparent_image(&resampled)?,
     };
 
+    let (image_ref, _) =
+        create_raster_image(chunk, ctx, &image, icc.as_deref(), interpolate)?;
+    Ok((image_ref, original_size))
+}
+
+/// Get the filter of the `image` crate that corresponds to a resampling filter.
+fn filter_type(filter: ResamplingFilter) -> FilterType {
+    match filter {
+        ResamplingFilter::Nearest => FilterType::Nearest,
+        ResamplingFilter::Triangle => FilterType::Triangle,
+        ResamplingFilter::CatmullRom => FilterType::CatmullRom,
+        ResamplingFilter::Gaussian => FilterType::Gaussian,
+        ResamplingFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+/// Calculate a hash of the content of an image.
+fn hash_image(kind: &ImageKind, ctx: &Context) -> u128 {
+    match kind {
+        ImageKind::JPEG(data) => hash128(&("jpeg", data.as_slice())),
+        ImageKind::PNG(data) => hash128(&("png", data.as_slice())),
+        ImageKind::GIF(data) => hash128(&("gif", data.as_slice())),
+        ImageKind::SVG(tree) => {
+            // The trees of the converted documents outlive the conversion, so the
+            // address of a unique one can't be reused by another tree.
+            let address = tree as *const Tree as usize;
+            if ctx.unique_svg_images.contains(&address) {
+                hash128(&("svg", address))
+            } else {
+                // Trees can't be hashed directly, so we hash their serialized form
+                // instead.
+                hash128(&("svg", tree.to_string(&WriteOptions::default())))
+            }
+        }
+    }
+}
+
+/// Find the SVG images in the given trees that can't have a duplicate, so that
+/// they don't need to be hashed by their content. usvg parses an image again for
+/// each `<use>` of it, so duplicates don't share their tree. They do share their
+/// size, view box and number of children though, so only images for which no
+/// other image has the same ones are unique.
+pub fn unique_svg_images<'a>(
+    trees: impl IntoIterator<Item = &'a Tree>,
+) -> HashSet<usize> {
+    fn collect(group: &Group, images: &mut HashMap<u128, Vec<usize>>) {
+        for child in group.children() {
+            match child {
+                Node::Group(group) => collect(group, images),
+                Node::Image(image) => {
+                    if let ImageKind::SVG(tree) = image.kind() {
+                        let size = tree.size();
+                        let rect = tree.view_box().rect;
+                        let bounds = [size.width(), size.height(), rect.x(), rect.y()];
+                        let key = hash128(&(
+                            bounds.map(f32::to_bits),
+                            [rect.width(), rect.height()].map(f32::to_bits),
+                            tree.root().children().len(),
+                        ));
+                        let address = tree as *const Tree as usize;
+                        images.entry(key).or_default().push(address);
+                        collect(tree.root(), images);
+                    }
+                }
+                _ => {}
+            }
+
+            child.subroots(|subroot| collect(subroot, images));
+        }
+    }
+
+    let mut images = HashMap::new();
+    for tree in trees {
+        collect(tree.root(), &mut images);
+    }
+
+    images
+        .into_values()
+        .filter_map(|addresses| match addresses[..] {
+            [address] => Some(address),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render a pixmap into a content stream, scaled to the given rect. In contrast to
+/// [`render`], the pixels are written directly instead of being encoded as a PNG
+/// first.
+#[cfg(feature = "filters")]
+pub fn render_pixmap(
+    pixmap: &tiny_skia::Pixmap,
+    rect: NonZeroRect,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    let image = encode_pixmap(pixmap, ctx.options.raster_jpeg_quality)?;
+    let (image_ref, image_size) = create_raster_image(chunk, ctx, &image, None, None)?;
+    let image_name = rc.add_x_object(image_ref);
+    let view_box = ViewBox { rect, aspect: AspectRatio::default() };
+    draw_image(&image_name, image_size, view_box, content);
+
+    Ok(())
+}
+
+/// Draw an image XObject of the given size into a view box.
+fn draw_image(
+    image_name: &Rc<String>,
+    image_size: Size,
+    view_box: ViewBox,
+    content: &mut Content,
+) {
     // Get the dimensions of the actual rect that is needed to scale the image into the image view
     // box. If the keepAspectRatio is slice, this rect will exceed the actual image view box, but
     // it will be clipped further below so that it always stays within the bounds of the actual image
@@ -109,12 +381,219 @@ pub fn render(
     content.restore_state();
 }
 
-fn handle_transparent_image(image: &DynamicImage) -> (Vec<u8>, Filter, Option<Vec<u8>>) {
+/// Decode an embedded raster image, together with its ICC profile, if it has one.
+fn decode(data: &[u8], format: ImageFormat) -> Result<(DynamicImage, Option<Vec<u8>>)> {
+    fn from_decoder<'a>(
+        mut decoder: impl ImageDecoder<'a>,
+    ) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+        let icc = decoder.icc_profile();
+        Ok((DynamicImage::from_decoder(decoder)?, icc))
+    }
+
+    let cursor = Cursor::new(data);
+    match format {
+        ImageFormat::Png => PngDecoder::new(cursor).and_then(from_decoder),
+        ImageFormat::Jpeg => JpegDecoder::new(cursor).and_then(from_decoder),
+        _ => image::load_from_memory_with_format(data, format).map(|image| (image, None)),
+    }
+    .map_err(|err| ConversionError::InvalidImage(err.to_string()))
+}
+
+/// The samples of a raster image, ready to be written into an image XObject.
+struct EncodedImage {
+    samples: Vec<u8>,
+    /// The filter the samples are encoded with.
+    filter: Filter,
+    /// Whether the samples still contain the filter bytes of PNG scanlines.
+    png_predictor: bool,
+    /// The transparency of the image, if it is not fully opaque.
+    alpha_mask: Option<AlphaMask>,
+    width: u32,
+    height: u32,
+    /// Whether the image is in RGB instead of grayscale.
+    has_color: bool,
+    /// The RGB colors the samples are indices into, if the image has a palette.
+    palette: Option<Vec<u8>>,
+    bits_per_component: i32,
+}
+
+/// The transparency of a raster image.
+enum AlphaMask {
+    /// A Flate-compressed alpha channel with the given number of bits per sample.
+    Soft(Vec<u8>, i32),
+    /// A Flate-compressed 1-bit mask, in which set bits mark transparent pixels.
+    Stencil(Vec<u8>),
+    /// The range of sample values that is transparent for each color component.
+    ColorKey(Vec<i32>),
+}
+
+fn handle_transparent_image(image: &DynamicImage) -> Result<EncodedImage> {
     let color = image.color();
     let bits = color.bits_per_pixel();
     let channels = color.channel_count() as u16;
 
-    let encoded_image: Vec<u8> = match (channels, bits / channels > 8) {
+    let (color_samples, alpha) = match image {
+        // The common formats with an alpha channel only need to be split into their
+        // color and alpha channels.
+        DynamicImage::ImageLumaA8(image) => split_alpha(image.as_raw(), 2, |x| [x]),
+        DynamicImage::ImageRgba8(image) => split_alpha(image.as_raw(), 4, |x| [x]),
+        DynamicImage::ImageLumaA16(image) => {
+            split_alpha(image.as_raw(), 2, u16::to_be_bytes)
+        }
+        DynamicImage::ImageRgba16(image) => {
+            split_alpha(image.as_raw(), 4, u16::to_be_bytes)
+        }
+        _ => (convert_color_channels(image)?, convert_alpha_channel(image)),
+    };
+
+    let depth = if bits / channels > 8 { 16 } else { 8 };
+    let alpha_mask = alpha.map(|alpha| encode_alpha(&alpha, depth, image.width()));
+    let (samples, palette, bits_per_component) = if depth == 8 {
+        compact_samples(color_samples, color.has_color(), image.width())
+    } else {
+        (color_samples, None, depth)
+    };
+
+    let compression_level = CompressionLevel::DefaultLevel as u8;
+    Ok(EncodedImage {
+        samples: compress_to_vec_zlib(&samples, compression_level),
+        filter: Filter::FlateDecode,
+        png_predictor: false,
+        alpha_mask,
+        width: image.width(),
+        height: image.height(),
+        has_color: color.has_color(),
+        palette,
+        bits_per_component,
+    })
+}
+
+/// Encode the alpha channel of an image that is not fully opaque. Alpha channels in
+/// which every pixel is either fully opaque or fully transparent are written as a
+/// stencil mask, which only needs a single bit per pixel.
+fn encode_alpha(alpha: &[u8], bits_per_component: i32, width: u32) -> AlphaMask {
+    let bytes = bits_per_component as usize / 8;
+    let is_binary = alpha
+        .chunks(bytes)
+        .all(|a| a.iter().all(|&b| b == 0) || a.iter().all(|&b| b == u8::MAX));
+
+    if is_binary {
+        let transparent: Vec<u8> =
+            alpha.chunks(bytes).map(|a| u8::from(a[0] == 0)).collect();
+        AlphaMask::Stencil(helper::deflate(&pack_samples(&transparent, width, 1)))
+    } else {
+        AlphaMask::Soft(helper::deflate(alpha), bits_per_component)
+    }
+}
+
+/// Reduce the size of 8-bit samples without losing information. Gray images whose
+/// samples fit into 1, 2 or 4 bits are written with that bit depth, while color
+/// images with at most 256 colors are written with a palette. Returns the samples,
+/// the palette and the number of bits per sample.
+fn compact_samples(
+    samples: Vec<u8>,
+    has_color: bool,
+    width: u32,
+) -> (Vec<u8>, Option<Vec<u8>>, i32) {
+    if has_color {
+        let Some((palette, indices)) = palettize(&samples) else {
+            return (samples, None, 8);
+        };
+
+        let colors = palette.len() / 3;
+        let bits = [1, 2, 4, 8].into_iter().find(|&bits| colors <= 1 << bits).unwrap();
+        (pack_samples(&indices, width, bits), Some(palette), i32::from(bits))
+    } else {
+        // The values of gray samples with fewer bits are scaled up evenly to 8 bits,
+        // e.g. 1-bit samples become 0 or 255.
+        let fits = |bits: u8| {
+            let step = u8::MAX / ((1 << bits) - 1);
+            samples.iter().all(|&s| s % step == 0).then_some((bits, step))
+        };
+
+        let Some((bits, step)) = [1, 2, 4].into_iter().find_map(fits) else {
+            return (samples, None, 8);
+        };
+
+        let values: Vec<u8> = samples.iter().map(|&s| s / step).collect();
+        (pack_samples(&values, width, bits), None, i32::from(bits))
+    }
+}
+
+/// Split RGB samples into a palette and indices into it, if there are at most 256
+/// different colors.
+fn palettize(samples: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut palette = vec![];
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(samples.len() / 3);
+
+    for color in samples.chunks_exact(3) {
+        let index = match lookup.get(color) {
+            Some(&index) => index,
+            None => {
+                let index = u8::try_from(lookup.len()).ok()?;
+                lookup.insert(color, index);
+                palette.extend_from_slice(color);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    Some((palette, indices))
+}
+
+/// Pack samples with the given number of bits into bytes. Each row starts at a new
+/// byte.
+fn pack_samples(samples: &[u8], width: u32, bits: u8) -> Vec<u8> {
+    if bits == 8 {
+        return samples.to_vec();
+    }
+
+    let per_byte = usize::from(8 / bits);
+    let mut packed = Vec::with_capacity(samples.len() / per_byte + 1);
+    for row in samples.chunks(width as usize) {
+        for group in row.chunks(per_byte) {
+            let mut byte = 0;
+            for (i, &sample) in group.iter().enumerate() {
+                byte |= sample << (8 - bits * (i as u8 + 1));
+            }
+            packed.push(byte);
+        }
+    }
+
+    packed
+}
+
+/// Split interleaved samples into their color channels and their alpha channel,
+/// which is the last one of each pixel. The alpha channel is only returned if the
+/// image is not fully opaque.
+fn split_alpha<T: Copy, const N: usize>(
+    samples: &[T],
+    channels: usize,
+    to_bytes: impl Fn(T) -> [u8; N],
+) -> (Vec<u8>, Option<Vec<u8>>) {
+    let pixels = samples.len() / channels;
+    let mut color = Vec::with_capacity(pixels * (channels - 1) * N);
+    let mut alpha = Vec::with_capacity(pixels * N);
+
+    for pixel in samples.chunks_exact(channels) {
+        let (&a, c) = pixel.split_last().unwrap();
+        color.extend(c.iter().flat_map(|&x| to_bytes(x)));
+        alpha.extend(to_bytes(a));
+    }
+
+    let opaque = alpha.iter().all(|&a| a == u8::MAX);
+    (color, (!opaque).then_some(alpha))
+}
+
+/// Convert the color channels of an image into 8 or 16 bit gray or RGB samples.
+fn convert_color_channels(image: &DynamicImage) -> Result<Vec<u8>> {
+    let color = image.color();
+    let bits = color.bits_per_pixel();
+    let channels = color.channel_count() as u16;
+
+    Ok(match (channels, bits / channels > 8) {
         (1 | 2, false) => image.to_luma8().pixels().flat_map(|&Luma(c)| c).collect(),
         (1 | 2, true) => image
             .to_luma16()
@@ -129,97 +608,553 @@ fn handle_transparent_image(image: &DynamicImage) -> (Vec<u8>, Filter, Option<Ve
             .flat_map(|&Rgb(c)| c)
             .flat_map(|x| x.to_be_bytes())
             .collect(),
-        _ => panic!("unknown number of channels={channels}"),
-    };
+        _ => {
+            return Err(ConversionError::InvalidImage(format!(
+                "unknown number of channels={channels}"
+            )))
+        }
+    })
+}
+
+/// Convert the alpha channel of an image into 8 or 16 bit samples, if the image is
+/// not fully opaque.
+fn convert_alpha_channel(image: &DynamicImage) -> Option<Vec<u8>> {
+    let color = image.color();
+    let bits = color.bits_per_pixel();
+    let channels = color.channel_count() as u16;
 
-    let encoded_mask: Option<Vec<u8>> = if color.has_alpha() {
-        if bits / channels > 8 {
-            let image = image.to_rgba16();
+    if !color.has_alpha() {
+        return None;
+    }
 
-            if image.pixels().any(|&Rgba([.., a])| a != u16::MAX) {
-                Some(image.pixels().flat_map(|&Rgba([.., a])| a.to_be_bytes()).collect())
-            } else {
-                None
-            }
+    if bits / channels > 8 {
+        let image = image.to_rgba16();
+
+        if image.pixels().any(|&Rgba([.., a])| a != u16::MAX) {
+            Some(image.pixels().flat_map(|&Rgba([.., a])| a.to_be_bytes()).collect())
         } else {
-            let image = image.to_rgba8();
+            None
+        }
+    } else {
+        let image = image.to_rgba8();
 
-            if image.pixels().any(|&Rgba([.., a])| a != u8::MAX) {
-                Some(image.pixels().map(|&Rgba([.., a])| a).collect())
-            } else {
-                None
-            }
+        if image.pixels().any(|&Rgba([.., a])| a != u8::MAX) {
+            Some(image.pixels().map(|&Rgba([.., a])| a).collect())
+        } else {
+            None
         }
+    }
+}
+
+/// Encode an opaque image as an 8 bit gray or RGB JPEG.
+fn encode_jpeg(image: &DynamicImage) -> Result<EncodedImage> {
+    let has_color = image.color().has_color();
+    let (samples, color_type) = if has_color {
+        (image.to_rgb8().into_raw(), ColorType::Rgb8)
     } else {
-        None
+        (image.to_luma8().into_raw(), ColorType::L8)
     };
 
-    let compression_level = CompressionLevel::DefaultLevel as u8;
-    let compressed_image = compress_to_vec_zlib(&encoded_image, compression_level);
-    let compressed_mask =
-        encoded_mask.map(|m| compress_to_vec_zlib(&m, compression_level));
+    let mut data = vec![];
+    JpegEncoder::new_with_quality(&mut data, RESAMPLED_JPEG_QUALITY)
+        .encode(&samples, image.width(), image.height(), color_type)
+        .map_err(|err| ConversionError::InvalidImage(err.to_string()))?;
+
+    Ok(EncodedImage {
+        samples: data,
+        filter: Filter::DctDecode,
+        png_predictor: false,
+        alpha_mask: None,
+        width: image.width(),
+        height: image.height(),
+        has_color,
+        palette: None,
+        bits_per_component: 8,
+    })
+}
 
-    (compressed_image, Filter::FlateDecode, compressed_mask)
+/// Split a pixmap into its color and alpha channels. If a JPEG quality is given, the
+/// color channels are encoded as a JPEG, otherwise they are Flate-compressed.
+#[cfg(feature = "filters")]
+fn encode_pixmap(
+    pixmap: &tiny_skia::Pixmap,
+    jpeg_quality: Option<u8>,
+) -> Result<EncodedImage> {
+    let mut color =
+        Vec::with_capacity(pixmap.width() as usize * pixmap.height() as usize * 3);
+    let mut alpha =
+        Vec::with_capacity(pixmap.width() as usize * pixmap.height() as usize);
+    for pixel in pixmap.pixels() {
+        let pixel = pixel.demultiply();
+        color.extend([pixel.red(), pixel.green(), pixel.blue()]);
+        alpha.push(pixel.alpha());
+    }
+
+    let (samples, filter) = match jpeg_quality {
+        Some(quality) => {
+            let mut data = vec![];
+            JpegEncoder::new_with_quality(&mut data, quality)
+                .encode(&color, pixmap.width(), pixmap.height(), ColorType::Rgb8)
+                .map_err(|err| ConversionError::InvalidImage(err.to_string()))?;
+            (data, Filter::DctDecode)
+        }
+        None => (helper::deflate(&color), Filter::FlateDecode),
+    };
+
+    let alpha_mask = alpha
+        .iter()
+        .any(|&a| a != u8::MAX)
+        .then(|| encode_alpha(&alpha, 8, pixmap.width()));
+
+    Ok(EncodedImage {
+        samples,
+        filter,
+        alpha_mask,
+        png_predictor: false,
+        width: pixmap.width(),
+        height: pixmap.height(),
+        has_color: true,
+        palette: None,
+        bits_per_component: 8,
+    })
 }
 
 fn create_raster_image(
     chunk: &mut Chunk,
     ctx: &mut Context,
-    samples: &[u8],
-    filter: Filter,
-    dynamic_image: &DynamicImage,
-    alpha_mask: Option<&[u8]>,
-    rc: &mut ResourceContainer,
-) -> (Rc<String>, Size) {
-    let color = dynamic_image.color();
-    let alpha_mask = alpha_mask.map(|mask_bytes| {
-        let soft_mask_id = ctx.alloc_ref();
-        let mut s_mask = chunk.image_xobject(soft_mask_id, mask_bytes);
-        s_mask.filter(filter);
-        s_mask.width(dynamic_image.width() as i32);
-        s_mask.height(dynamic_image.height() as i32);
-        s_mask.color_space().device_gray();
-        s_mask.bits_per_component(calculate_bits_per_component(color));
-        soft_mask_id
-    });
+    image: &EncodedImage,
+    icc: Option<&[u8]>,
+    interpolate: Option<bool>,
+) -> Result<(Ref, Size)> {
+    // The header of an image that is embedded without decoding it may claim a
+    // size of zero.
+    let image_size =
+        Size::from_wh(image.width as f32, image.height as f32).ok_or_else(|| {
+            ConversionError::InvalidImage("image has a size of zero".to_string())
+        })?;
+
+    let mask_ref = match &image.alpha_mask {
+        Some(AlphaMask::Soft(mask_bytes, bits_per_component)) => {
+            let soft_mask_id = ctx.alloc_ref();
+            let mut s_mask = chunk.image_xobject(soft_mask_id, mask_bytes);
+            s_mask.filter(Filter::FlateDecode);
+            s_mask.width(image.width as i32);
+            s_mask.height(image.height as i32);
+            // Soft masks must always use the DeviceGray color space, even in PDF/A.
+            s_mask.color_space().device_gray();
+            s_mask.bits_per_component(*bits_per_component);
+            if let Some(interpolate) = interpolate {
+                s_mask.interpolate(interpolate);
+            }
+            Some(soft_mask_id)
+        }
+        Some(AlphaMask::Stencil(mask_bytes)) => {
+            let stencil_mask_id = ctx.alloc_ref();
+            let mut mask = chunk.image_xobject(stencil_mask_id, mask_bytes);
+            mask.filter(Filter::FlateDecode);
+            mask.width(image.width as i32);
+            mask.height(image.height as i32);
+            mask.image_mask(true);
+            mask.bits_per_component(1);
+            Some(stencil_mask_id)
+        }
+        Some(AlphaMask::ColorKey(_)) | None => None,
+    };
+
+    let image_ref = ctx.alloc_ref();
+
+    let mut image_x_object = chunk.image_xobject(image_ref, &image.samples);
+    image_x_object.filter(image.filter);
+    image_x_object.width(image.width as i32);
+    image_x_object.height(image.height as i32);
+
+    // Use the embedded ICC profile if it matches the color type of the image,
+    // and assume sRGB otherwise.
+    let components = if image.has_color { 3 } else { 1 };
+    let color_space_ref = match icc.filter(|icc| is_icc_profile_for(icc, components)) {
+        Some(icc) => ctx.icc_profile_ref(icc, components as i32),
+        None if image.has_color => ctx.srgb_ref(),
+        None => ctx.sgray_ref(),
+    };
+    match &image.palette {
+        Some(palette) => {
+            let mut color_space = image_x_object.insert(Name(b"ColorSpace")).array();
+            color_space.item(Name(b"Indexed"));
+            color_space
+                .push()
+                .array()
+                .item(Name(b"ICCBased"))
+                .item(color_space_ref);
+            color_space.item(palette.len() as i32 / 3 - 1);
+            color_space.item(Str(palette));
+        }
+        None => image_x_object.color_space().icc_based(color_space_ref),
+    }
+
+    image_x_object.bits_per_component(image.bits_per_component);
+    if image.png_predictor {
+        let colors = if image.palette.is_some() { 1 } else { components };
+        image_x_object
+            .insert(Name(b"DecodeParms"))
+            .dict()
+            .pair(Name(b"Predictor"), 15)
+            .pair(Name(b"Colors"), i32::from(colors))
+            .pair(Name(b"BitsPerComponent"), image.bits_per_component)
+            .pair(Name(b"Columns"), image.width as i32);
+    }
+    if let Some(interpolate) = interpolate {
+        image_x_object.interpolate(interpolate);
+    }
+    match (&image.alpha_mask, mask_ref) {
+        (Some(AlphaMask::Soft(..)), Some(mask_ref)) => {
+            image_x_object.s_mask(mask_ref);
+        }
+        (Some(AlphaMask::Stencil(_)), Some(mask_ref)) => {
+            image_x_object.stencil_mask(mask_ref);
+        }
+        (Some(AlphaMask::ColorKey(ranges)), _) => {
+            image_x_object.color_mask(ranges.iter().copied());
+        }
+        _ => {}
+    }
+    image_x_object.finish();
+    Ok((image_ref, image_size))
+}
+
+/// Check whether an ICC profile is well-formed and describes a color space with
+/// the given number of components.
+fn is_icc_profile_for(icc: &[u8], components: u8) -> bool {
+    if icc.len() < 128 || &icc[36..40] != b"acsp" {
+        return false;
+    }
+
+    let color_space = &icc[16..20];
+    match components {
+        1 => color_space == b"GRAY",
+        3 => color_space == b"RGB ",
+        4 => color_space == b"CMYK",
+        _ => false,
+    }
+}
+
+/// Embed a JPEG image without decoding it.
+fn create_jpeg_image(
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+    data: &[u8],
+    interpolate: Option<bool>,
+) -> Result<(Ref, Size)> {
+    let info = JpegInfo::parse(data).ok_or_else(|| {
+        ConversionError::InvalidImage("failed to read JPEG header".to_string())
+    })?;
+
+    if info.precision != 8 {
+        return Err(ConversionError::InvalidImage(format!(
+            "unsupported JPEG precision of {} bits",
+            info.precision
+        )));
+    }
 
     let image_size =
-        Size::from_wh(dynamic_image.width() as f32, dynamic_image.height() as f32)
-            .unwrap();
+        Size::from_wh(info.width as f32, info.height as f32).ok_or_else(|| {
+            ConversionError::InvalidImage("JPEG has a size of zero".to_string())
+        })?;
+
+    // `None` stands for DeviceCMYK, for which we don't have a default profile.
+    let icc = info
+        .icc
+        .as_deref()
+        .filter(|icc| is_icc_profile_for(icc, info.components));
+    let color_space_ref = match (icc, info.components) {
+        (Some(icc), n) => Some(ctx.icc_profile_ref(icc, n as i32)),
+        (None, 1) => Some(ctx.sgray_ref()),
+        (None, 3) => Some(ctx.srgb_ref()),
+        // PDF/A only allows DeviceCMYK if the output intent is CMYK as well.
+        (None, 4) if ctx.options.pdfa && ctx.cmyk.is_none() => {
+            return Err(ConversionError::PdfA(
+                "CMYK JPEG images need an embedded ICC profile".to_string(),
+            ))
+        }
+        (None, 4) => None,
+        (None, n) => {
+            return Err(ConversionError::InvalidImage(format!(
+                "unsupported number of JPEG components={n}"
+            )))
+        }
+    };
+
     let image_ref = ctx.alloc_ref();
-    let image_name = rc.add_x_object(image_ref);
 
-    let mut image_x_object = chunk.image_xobject(image_ref, samples);
-    image_x_object.filter(filter);
-    image_x_object.width(dynamic_image.width() as i32);
-    image_x_object.height(dynamic_image.height() as i32);
+    let mut image_x_object = chunk.image_xobject(image_ref, data);
+    image_x_object.filter(Filter::DctDecode);
+    image_x_object.width(info.width as i32);
+    image_x_object.height(info.height as i32);
+    image_x_object.bits_per_component(8);
+    if let Some(interpolate) = interpolate {
+        image_x_object.interpolate(interpolate);
+    }
 
-    let color_space = image_x_object.color_space();
-    if color.has_color() {
-        color_space.device_rgb();
-    } else {
-        color_space.device_gray();
+    match color_space_ref {
+        Some(color_space_ref) => image_x_object.color_space().icc_based(color_space_ref),
+        None => image_x_object.color_space().device_cmyk(),
     }
 
-    image_x_object.bits_per_component(calculate_bits_per_component(color));
-    if let Some(soft_mask_id) = alpha_mask {
-        image_x_object.s_mask(soft_mask_id);
+    if let Some(transform) = info.adobe_transform {
+        // Adobe applications write CMYK JPEGs with inverted values.
+        if info.components == 4 {
+            image_x_object.decode([1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        }
+
+        // Not all viewers respect the transform of the Adobe marker, so we make it
+        // explicit.
+        image_x_object
+            .insert(Name(b"DecodeParms"))
+            .dict()
+            .pair(Name(b"ColorTransform"), i32::from(transform != 0));
     }
+
     image_x_object.finish();
-    (image_name, image_size)
+    Ok((image_ref, image_size))
 }
 
-fn calculate_bits_per_component(color_type: ColorType) -> i32 {
-    (color_type.bits_per_pixel() / color_type.channel_count() as u16) as i32
+/// Information about a PNG image that is read from its chunks.
+struct PngInfo {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+    /// The RGB colors from the `PLTE` chunk, if there is one.
+    palette: Option<Vec<u8>>,
+    /// The content of the `tRNS` chunk, i.e. the transparent color or the alpha
+    /// values of the palette, if there is one.
+    transparency: Option<Vec<u8>>,
+    /// The ICC profile from the `iCCP` chunk, if there is one.
+    icc: Option<Vec<u8>>,
+    /// The concatenated data of all `IDAT` chunks.
+    data: Vec<u8>,
+}
+
+impl PngInfo {
+    const GRAY: u8 = 0;
+    const RGB: u8 = 2;
+    const PALETTE: u8 = 3;
+
+    /// Read the chunks of a PNG image.
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut rest = data.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+        let mut header = None;
+        let mut palette = None;
+        let mut transparency = None;
+        let mut icc = None;
+        let mut idat = vec![];
+
+        while rest.len() >= 12 {
+            let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+            let kind = &rest[4..8];
+            let body = rest.get(8..8 + length)?;
+            // Skip the chunk together with its CRC.
+            rest = rest.get(12 + length..)?;
+
+            match kind {
+                // We can't interpret other compression or filter methods.
+                b"IHDR" if body.len() == 13 && body[10] == 0 && body[11] == 0 => {
+                    let width = u32::from_be_bytes(body[..4].try_into().ok()?);
+                    let height = u32::from_be_bytes(body[4..8].try_into().ok()?);
+                    header = Some((width, height, body[8], body[9], body[12] != 0));
+                }
+                b"PLTE" => palette = Some(body.to_vec()),
+                b"tRNS" => transparency = Some(body.to_vec()),
+                b"iCCP" => {
+                    // The profile name is followed by a null byte and the compression
+                    // method.
+                    let start = body.iter().position(|&b| b == 0)? + 2;
+                    icc = decompress_to_vec_zlib(body.get(start..)?).ok();
+                }
+                b"IDAT" => idat.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        let (width, height, bit_depth, color_type, interlaced) = header?;
+        Some(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced,
+            palette,
+            transparency,
+            icc,
+            data: idat,
+        })
+    }
+
+    /// Turn the image into samples that can be embedded as is, since PDF supports the
+    /// same predictors as PNG. This is only possible for non-interlaced gray, RGB or
+    /// palette images whose transparency can be expressed as a color key mask.
+    fn into_image(self) -> Option<(EncodedImage, Option<Vec<u8>>)> {
+        if self.interlaced || self.data.is_empty() {
+            return None;
+        }
+
+        let (has_color, palette, alpha_mask) = match self.color_type {
+            Self::GRAY | Self::RGB => {
+                // The transparent color has a 16-bit sample for each component.
+                let alpha_mask = self.transparency.map(|color| {
+                    let samples = color.chunks_exact(2);
+                    AlphaMask::ColorKey(
+                        samples
+                            .map(|s| i32::from(u16::from_be_bytes([s[0], s[1]])))
+                            .flat_map(|s| [s, s])
+                            .collect(),
+                    )
+                });
+                (self.color_type == Self::RGB, None, alpha_mask)
+            }
+            Self::PALETTE => {
+                let palette =
+                    self.palette.filter(|p| !p.is_empty() && p.len() % 3 == 0)?;
+                let alpha_mask = match self.transparency {
+                    Some(alphas) => palette_color_key(&alphas)?,
+                    None => None,
+                };
+                (true, Some(palette), alpha_mask)
+            }
+            _ => return None,
+        };
+
+        let image = EncodedImage {
+            samples: self.data,
+            filter: Filter::FlateDecode,
+            png_predictor: true,
+            alpha_mask,
+            width: self.width,
+            height: self.height,
+            has_color,
+            palette,
+            bits_per_component: self.bit_depth as i32,
+        };
+
+        Some((image, self.icc))
+    }
+}
+
+/// Express the alpha values of the palette entries of an image as a color key mask.
+/// Returns `None` if that is not possible, i.e. if some entries are semi-transparent or
+/// the transparent entries don't form a single range, and `Some(None)` if the image is
+/// opaque.
+fn palette_color_key(alphas: &[u8]) -> Option<Option<AlphaMask>> {
+    if alphas.iter().any(|&a| a != 0 && a != u8::MAX) {
+        return None;
+    }
+
+    let first = alphas.iter().position(|&a| a == 0);
+    let last = alphas.iter().rposition(|&a| a == 0);
+    match first.zip(last) {
+        Some((first, last)) if alphas[first..=last].iter().all(|&a| a == 0) => {
+            Some(Some(AlphaMask::ColorKey(vec![first as i32, last as i32])))
+        }
+        Some(_) => None,
+        None => Some(None),
+    }
+}
+
+/// Information about a JPEG image that is read from its markers.
+struct JpegInfo {
+    width: u16,
+    height: u16,
+    precision: u8,
+    components: u8,
+    /// The color transform from the Adobe APP14 marker, if there is one.
+    adobe_transform: Option<u8>,
+    /// The ICC profile from the APP2 markers, if there is one.
+    icc: Option<Vec<u8>>,
+}
+
+impl JpegInfo {
+    /// Read the markers of a JPEG image up to the start of the scan.
+    fn parse(data: &[u8]) -> Option<Self> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+
+        let mut frame = None;
+        let mut adobe_transform = None;
+        let mut icc_chunks = vec![];
+        let mut pos = 2;
+
+        loop {
+            if *data.get(pos)? != 0xFF {
+                return None;
+            }
+
+            let marker = *data.get(pos + 1)?;
+            pos += 2;
+
+            match marker {
+                // Fill bytes.
+                0xFF => pos -= 1,
+                // Markers without a segment.
+                0x01 | 0xD0..=0xD7 => {}
+                // Start of scan or end of image.
+                0xDA | 0xD9 => break,
+                _ => {
+                    let length =
+                        u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]);
+                    let segment = data.get(pos + 2..pos + usize::from(length))?;
+                    pos += usize::from(length);
+
+                    match marker {
+                        // Start of frame, except for DHT, JPG and DAC.
+                        0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                            frame = Some((
+                                *segment.first()?,
+                                u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]),
+                                u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]),
+                                *segment.get(5)?,
+                            ));
+                        }
+                        0xE2 => {
+                            if let Some(chunk) = segment.strip_prefix(b"ICC_PROFILE\0") {
+                                let sequence = *chunk.first()?;
+                                icc_chunks.push((sequence, chunk.get(2..)?));
+                            }
+                        }
+                        0xEE if segment.starts_with(b"Adobe") => {
+                            adobe_transform = Some(*segment.get(11)?);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let (precision, height, width, components) = frame?;
+
+        // Large ICC profiles are split over multiple markers.
+        icc_chunks.sort_by_key(|&(sequence, _)| sequence);
+        let icc = (!icc_chunks.is_empty()).then(|| {
+            icc_chunks.into_iter().flat_map(|(_, chunk)| chunk).copied().collect()
+        });
+
+        Some(Self {
+            width,
+            height,
+            precision,
+            components,
+            adobe_transform,
+            icc,
+        })
+    }
 }
 
 fn create_svg_image(
     tree: &Tree,
     chunk: &mut Chunk,
     ctx: &mut Context,
-    rc: &mut ResourceContainer,
-) -> (Rc<String>, Size) {
-    let image_ref = tree_to_xobject(tree, chunk, ctx);
-    let image_name = rc.add_x_object(image_ref);
-    (image_name, tree.size())
+) -> Result<(Ref, Size)> {
+    let image_ref = tree_to_xobject(tree, chunk, ctx, None)?;
+    Ok((image_ref, tree.size()))
 }
@@ -5,6 +5,8 @@ use super::group;
 use crate::util::context::Context;
 use crate::util::helper::{clip_to_rect, MaskTypeExt, NameExt, RectExt};
 use crate::util::resources::ResourceContainer;
+use crate::util::tags::{self, StreamTags};
+use crate::Result;
 
 /// Render a mask into a content stream.
 pub fn render(
@@ -14,22 +16,31 @@ pub fn render(
     content: &mut Content,
     ctx: &mut Context,
     rc: &mut ResourceContainer,
-) {
-    let mask_ref = create(group, mask, chunk, ctx);
+) -> Result<()> {
+    let mask_ref = create(group, mask, chunk, ctx)?;
     let mask_name = rc.add_graphics_state(mask_ref);
     content.set_parameters(mask_name.to_pdf_name());
+
+    Ok(())
 }
 
 /// Create a mask and return the object reference to it.
-pub fn create(parent: &Group, mask: &Mask, chunk: &mut Chunk, ctx: &mut Context) -> Ref {
+pub fn create(
+    parent: &Group,
+    mask: &Mask,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+) -> Result<Ref> {
     let x_ref = ctx.alloc_ref();
     let mut rc = ResourceContainer::new();
+    // The content of masks can't be part of the structure tree.
+    rc.tags = ctx.options.tagged.then(|| StreamTags::new(Some(x_ref)));
 
     let mut content = Content::new();
     content.save_state();
 
     if let Some(mask) = mask.mask() {
-        render(parent, mask, chunk, &mut content, ctx, &mut rc);
+        render(parent, mask, chunk, &mut content, ctx, &mut rc)?;
     }
 
     let rect = mask.rect();
@@ -38,15 +49,19 @@ pub fn create(parent: &Group, mask: &Mask, chunk: &mut Chunk, ctx: &mut Context)
     // circumvent a bug in Firefox where the bounding box is not applied properly for some transforms.
     // If we don't do this, the "half-width-region-with-rotation.svg" test case won't render properly.
     clip_to_rect(rect, &mut content);
-    group::render(
-        mask.root(),
-        chunk,
-        &mut content,
-        ctx,
-        Transform::default(),
-        None,
-        &mut rc,
-    );
+    tags::render_artifact(&mut content, &mut rc, |content, rc| {
+        ctx.with_root(None, None, |ctx| {
+            group::render(
+                mask.root(),
+                chunk,
+                content,
+                ctx,
+                Transform::default(),
+                None,
+                rc,
+            )
+        })
+    })?;
 
     content.restore_state();
     let content_stream = ctx.finish_content(content);
@@ -64,7 +79,7 @@ pub fn create(parent: &Group, mask: &Mask, chunk: &mut Chunk, ctx: &mut Context)
         .isolated(false)
         .knockout(false)
         .color_space()
-        .icc_based(ctx.srgb_ref());
+        .icc_based(ctx.color_space_ref());
 
     x_object.bbox(rect.to_pdf_rect());
     x_object.finish();
@@ -73,5 +88,5 @@ pub fn create(parent: &Group, mask: &Mask, chunk: &mut Chunk, ctx: &mut Context)
     let mut gs = chunk.ext_graphics(gs_ref);
     gs.soft_mask().subtype(mask.kind().to_pdf_mask_type()).group(x_ref);
 
-    gs_ref
+    Ok(gs_ref)
 }
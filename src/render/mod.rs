@@ -1,9 +1,12 @@
-use pdf_writer::{Chunk, Content, Filter, Finish, Ref};
+use pdf_writer::types::StructRole;
+use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref};
 use usvg::{Node, Transform, Tree};
 
 use crate::util::context::Context;
-use crate::util::helper::{RectExt, TransformExt};
+use crate::util::helper::{ContentExt, RectExt, TransformExt};
 use crate::util::resources::ResourceContainer;
+use crate::util::tags;
+use crate::{ConversionError, Result};
 
 pub mod clip_path;
 #[cfg(feature = "filters")]
@@ -19,14 +22,16 @@ pub mod pattern;
 pub mod text;
 
 /// Write a tree into a stream. Assumes that the stream belongs to transparency group and the object
-/// that contains it has the correct bounding box set.
+/// that contains it has the correct bounding box set. `canvas_transform` maps the canvas of the
+/// tree into PDF points on the page, if that is known.
 pub fn tree_to_stream(
     tree: &Tree,
     chunk: &mut Chunk,
     content: &mut Content,
     ctx: &mut Context,
+    canvas_transform: Option<Transform>,
     rc: &mut ResourceContainer,
-) {
+) -> Result<()> {
     content.save_state();
 
     let initial_transform =
@@ -37,19 +42,34 @@ pub fn tree_to_stream(
 
     content.transform(initial_transform.to_pdf_transform());
 
-    group::render(tree.root(), chunk, content, ctx, initial_transform, None, rc);
+    // Only the part of the tree inside of its canvas can be visible.
+    let visible_area = tree.size().to_non_zero_rect(0.0, 0.0).transform(
+        tree.view_box().to_transform(tree.size()).invert().unwrap_or_default(),
+    );
+    let root_transform = canvas_transform
+        .map(|ts| ts.pre_concat(tree.view_box().to_transform(tree.size())));
+    ctx.with_root(visible_area, root_transform, |ctx| {
+        group::render(tree.root(), chunk, content, ctx, initial_transform, None, rc)
+    })?;
     content.restore_state();
+
+    Ok(())
 }
 
 /// Convert a tree into a XObject of size 1x1, similar to an image.
-pub fn tree_to_xobject(tree: &Tree, chunk: &mut Chunk, ctx: &mut Context) -> Ref {
+pub fn tree_to_xobject(
+    tree: &Tree,
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+    canvas_transform: Option<Transform>,
+) -> Result<Ref> {
     let bbox = tree.size().to_non_zero_rect(0.0, 0.0);
     let x_ref = ctx.alloc_ref();
 
     let mut rc = ResourceContainer::new();
 
     let mut content = Content::new();
-    tree_to_stream(tree, chunk, &mut content, ctx, &mut rc);
+    tree_to_stream(tree, chunk, &mut content, ctx, canvas_transform, &mut rc)?;
     let stream = ctx.finish_content(content);
 
     let mut x_object = chunk.form_xobject(x_ref, &stream);
@@ -66,9 +86,12 @@ pub fn tree_to_xobject(tree: &Tree, chunk: &mut Chunk, ctx: &mut Context) -> Ref
     resources.finish();
     x_object.finish();
 
-    x_ref
+    Ok(x_ref)
 }
 
+/// The role and the marked-content tag of figures.
+const FIGURE: (StructRole, Name) = (StructRole::Figure, Name(b"Figure"));
+
 trait Render {
     fn render(
         &self,
@@ -77,7 +100,7 @@ trait Render {
         ctx: &mut Context,
         accumulated_transform: Transform,
         rc: &mut ResourceContainer,
-    );
+    ) -> Result<()>;
 }
 
 impl Render for Node {
@@ -88,48 +111,147 @@ impl Render for Node {
         ctx: &mut Context,
         accumulated_transform: Transform,
         rc: &mut ResourceContainer,
-    ) {
-        match self {
-            Node::Path(ref path) => {
-                path::render(path, chunk, content, ctx, rc, accumulated_transform)
+    ) -> Result<()> {
+        // In lenient mode, nodes that can fail on their own, i.e. images and text, are
+        // skipped if they do. All other nodes can only fail because of such a node
+        // or in rare cases, which are caught at the level of the top-level nodes.
+        let skippable = ctx.options.lenient
+            && (!ctx.in_skippable_node || matches!(self, Node::Image(_) | Node::Text(_)));
+        if !skippable {
+            return render_node(self, chunk, content, ctx, accumulated_transform, rc);
+        }
+
+        // The node is rendered into a content stream and chunk of its own, so that a
+        // node that fails halfway through can be skipped without leaving unbalanced
+        // operators, unused resources or orphaned objects behind.
+        let mut node_chunk = Chunk::new();
+        let mut node_content = Content::new();
+        let node_rc = rc.clone();
+        let checkpoint = ctx.checkpoint();
+        let in_skippable_node = std::mem::replace(&mut ctx.in_skippable_node, true);
+        let result = render_node(
+            self,
+            &mut node_chunk,
+            &mut node_content,
+            ctx,
+            accumulated_transform,
+            rc,
+        );
+        ctx.in_skippable_node = in_skippable_node;
+
+        match result {
+            Ok(()) => {
+                chunk.extend(&node_chunk);
+                content.append(node_content);
+                Ok(())
             }
-            Node::Group(ref group) => {
-                group::render(group, chunk, content, ctx, accumulated_transform, None, rc)
+            // PDF/A violations are reported independently of the lenient mode.
+            Err(err @ ConversionError::PdfA(_)) => Err(err),
+            Err(err) => {
+                log::warn!("Failed to convert node: {err}. Skipping.");
+                *rc = node_rc;
+                ctx.reset(checkpoint);
+                Ok(())
             }
-            #[cfg(feature = "image")]
-            Node::Image(ref image) => image::render(
-                image.visibility(),
-                image.kind(),
-                image.view_box(),
-                chunk,
+        }
+    }
+}
+
+fn render_node(
+    node: &Node,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    accumulated_transform: Transform,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    match node {
+        Node::Path(ref path) => tags::render_artifact(content, rc, |content, rc| {
+            path::render(path, chunk, content, ctx, rc, accumulated_transform)
+        }),
+        Node::Group(ref group) => match ctx.options.alt_texts.get(group.id()) {
+            Some(alt) => tags::render_element(
                 content,
                 ctx,
                 rc,
-            ),
-            #[cfg(not(feature = "image"))]
-            Node::Image(_) => {
-                log::warn!("Failed convert image because the image feature was disabled. Skipping.")
-            }
-            #[cfg(feature = "text")]
-            Node::Text(ref text) => {
-                if ctx.options.embed_text {
-                    text::render(text, chunk, content, ctx, rc, accumulated_transform);
-                } else {
+                FIGURE,
+                Some(alt.clone()),
+                None,
+                |content, ctx, rc| {
                     group::render(
-                        text.flattened(),
+                        group,
                         chunk,
                         content,
                         ctx,
                         accumulated_transform,
                         None,
                         rc,
-                    );
-                }
+                    )
+                },
+            ),
+            None => {
+                group::render(group, chunk, content, ctx, accumulated_transform, None, rc)
             }
-            #[cfg(not(feature = "text"))]
-            Node::Text(_) => {
-                log::warn!("Failed convert text because the text feature was disabled. Skipping.")
+        },
+        #[cfg(feature = "image")]
+        Node::Image(ref image) => match ctx.options.alt_texts.get(image.id()) {
+            Some(alt) => tags::render_element(
+                content,
+                ctx,
+                rc,
+                FIGURE,
+                Some(alt.clone()),
+                None,
+                |content, ctx, rc| image::render(image, chunk, content, ctx, rc),
+            ),
+            // Images without an alternative text are considered decorative.
+            None => tags::render_artifact(content, rc, |content, rc| {
+                image::render(image, chunk, content, ctx, rc)
+            }),
+        },
+        #[cfg(not(feature = "image"))]
+        Node::Image(_) => {
+            log::warn!(
+                "Failed convert image because the image feature was disabled. Skipping."
+            );
+            Ok(())
+        }
+        #[cfg(feature = "text")]
+        Node::Text(ref text) => {
+            if ctx.options.embed_text {
+                text::render(text, chunk, content, ctx, rc, accumulated_transform)
+            } else {
+                // The text can't be extracted from the paths, so it is attached
+                // to the paragraph instead.
+                let actual_text =
+                    text.chunks().iter().map(|chunk| chunk.text()).collect();
+                tags::render_element(
+                    content,
+                    ctx,
+                    rc,
+                    (StructRole::P, Name(b"P")),
+                    None,
+                    Some(actual_text),
+                    |content, ctx, rc| {
+                        group::render(
+                            text.flattened(),
+                            chunk,
+                            content,
+                            ctx,
+                            accumulated_transform,
+                            None,
+                            rc,
+                        )
+                    },
+                )
             }
         }
+        #[cfg(not(feature = "text"))]
+        Node::Text(_) => {
+            log::warn!(
+                "Failed convert text because the text feature was disabled. Skipping."
+            );
+            Ok(())
+        }
     }
 }
@@ -10,6 +10,7 @@ use super::{gradient, pattern};
 use crate::util::context::Context;
 use crate::util::helper::{ColorExt, LineCapExt, LineJoinExt, NameExt};
 use crate::util::resources::ResourceContainer;
+use crate::Result;
 
 /// Render a path into a content stream.
 pub fn render(
@@ -19,9 +20,9 @@ pub fn render(
     ctx: &mut Context,
     rc: &mut ResourceContainer,
     accumulated_transform: Transform,
-) {
+) -> Result<()> {
     if path.visibility() != Visibility::Visible {
-        return;
+        return Ok(());
     }
 
     // In order to support different stroke and fill orders as well as "advanced" paths
@@ -30,14 +31,16 @@ pub fn render(
     // higher file sizes depending on the SVG.
     match path.paint_order() {
         PaintOrder::FillAndStroke => {
-            fill_path(path, chunk, content, ctx, rc, accumulated_transform);
-            stroke_path(path, chunk, content, ctx, rc, accumulated_transform);
+            fill_path(path, chunk, content, ctx, rc, accumulated_transform)?;
+            stroke_path(path, chunk, content, ctx, rc, accumulated_transform)?;
         }
         PaintOrder::StrokeAndFill => {
-            stroke_path(path, chunk, content, ctx, rc, accumulated_transform);
-            fill_path(path, chunk, content, ctx, rc, accumulated_transform);
+            stroke_path(path, chunk, content, ctx, rc, accumulated_transform)?;
+            fill_path(path, chunk, content, ctx, rc, accumulated_transform)?;
         }
     }
+
+    Ok(())
 }
 
 /// Draws a path into a content stream. Note that this does not perform any stroking/filling,
@@ -93,9 +96,9 @@ pub(crate) fn stroke_path(
     ctx: &mut Context,
     rc: &mut ResourceContainer,
     accumulated_transform: Transform,
-) {
+) -> Result<()> {
     if path.data().bounds().width() == 0.0 && path.data().bounds().height() == 0.0 {
-        return;
+        return Ok(());
     }
 
     let operation = |content: &mut Content, stroke: &Stroke| {
@@ -113,8 +116,10 @@ pub(crate) fn stroke_path(
             operation,
             accumulated_transform,
             path.stroke_bounding_box(),
-        );
+        )?;
     }
+
+    Ok(())
 }
 
 /// Prepare the stroke color and then perform some operation (either drawing text or
@@ -129,7 +134,7 @@ pub(crate) fn stroke(
     operation: impl Fn(&mut Content, &Stroke),
     accumulated_transform: Transform,
     bbox: Rect,
-) {
+) -> Result<()> {
     let paint = &stroke.paint();
 
     content.save_state();
@@ -137,10 +142,11 @@ pub(crate) fn stroke(
     match paint {
         Paint::Color(c) => {
             set_opacity_gs(chunk, content, ctx, Some(stroke.opacity()), None, rc);
-            let srgb_name = rc.add_color_space(ctx.srgb_ref());
-            let srgb_name = ColorSpaceOperand::Named(srgb_name.to_pdf_name());
-            content.set_stroke_color_space(srgb_name);
-            content.set_stroke_color(c.to_pdf_color());
+            let color_space_name = rc.add_color_space(ctx.color_space_ref());
+            let color_space_name =
+                ColorSpaceOperand::Named(color_space_name.to_pdf_name());
+            content.set_stroke_color_space(color_space_name);
+            content.set_stroke_color(ctx.convert_color(c.to_pdf_color()));
         }
         Paint::Pattern(p) => {
             // Instead of setting the opacity via an external graphics state, we to it
@@ -154,7 +160,7 @@ pub(crate) fn stroke(
                 ctx,
                 accumulated_transform,
                 Some(stroke.opacity()),
-            );
+            )?;
             let pattern_name = rc.add_pattern(pattern_ref);
             content.set_stroke_color_space(Pattern);
             content.set_stroke_pattern(None, pattern_name.to_pdf_name());
@@ -183,6 +189,7 @@ pub(crate) fn stroke(
                 chunk,
                 ctx,
                 &accumulated_transform,
+                bbox,
             );
             let pattern_name = rc.add_pattern(pattern_ref);
             content.set_stroke_color_space(Pattern);
@@ -204,6 +211,8 @@ pub(crate) fn stroke(
     operation(content, stroke);
 
     content.restore_state();
+
+    Ok(())
 }
 
 /// Draws a filled path into the content stream.
@@ -214,9 +223,9 @@ pub(crate) fn fill_path(
     ctx: &mut Context,
     rc: &mut ResourceContainer,
     accumulated_transform: Transform,
-) {
+) -> Result<()> {
     if path.data().bounds().width() == 0.0 || path.data().bounds().height() == 0.0 {
-        return;
+        return Ok(());
     }
 
     let operation = |content: &mut Content, fill: &Fill| {
@@ -234,8 +243,10 @@ pub(crate) fn fill_path(
             operation,
             accumulated_transform,
             path.bounding_box(),
-        );
+        )?;
     }
+
+    Ok(())
 }
 
 /// Prepare the fill color and then perform some operation (either drawing text or
@@ -250,7 +261,7 @@ pub(crate) fn fill(
     operation: impl Fn(&mut Content, &Fill),
     accumulated_transform: Transform,
     bbox: Rect,
-) {
+) -> Result<()> {
     let paint = &fill.paint();
 
     content.save_state();
@@ -258,10 +269,11 @@ pub(crate) fn fill(
     match paint {
         Paint::Color(c) => {
             set_opacity_gs(chunk, content, ctx, None, Some(fill.opacity()), rc);
-            let srgb_name = rc.add_color_space(ctx.srgb_ref());
-            let srgb_name = ColorSpaceOperand::Named(srgb_name.to_pdf_name());
-            content.set_fill_color_space(srgb_name);
-            content.set_fill_color(c.to_pdf_color());
+            let color_space_name = rc.add_color_space(ctx.color_space_ref());
+            let color_space_name =
+                ColorSpaceOperand::Named(color_space_name.to_pdf_name());
+            content.set_fill_color_space(color_space_name);
+            content.set_fill_color(ctx.convert_color(c.to_pdf_color()));
         }
         Paint::Pattern(p) => {
             // See note in the `stroke` function.
@@ -271,7 +283,7 @@ pub(crate) fn fill(
                 ctx,
                 accumulated_transform,
                 Some(fill.opacity()),
-            );
+            )?;
             let pattern_name = rc.add_pattern(pattern_ref);
             content.set_fill_color_space(Pattern);
             content.set_fill_pattern(None, pattern_name.to_pdf_name());
@@ -291,6 +303,7 @@ pub(crate) fn fill(
                 chunk,
                 ctx,
                 &accumulated_transform,
+                bbox,
             );
             let pattern_name = rc.add_pattern(pattern_ref);
             content.set_fill_color_space(Pattern);
@@ -300,6 +313,8 @@ pub(crate) fn fill(
 
     operation(content, fill);
     content.restore_state();
+
+    Ok(())
 }
 
 fn finish_path(stroke: Option<&Stroke>, fill: Option<&Fill>, content: &mut Content) {
@@ -314,7 +329,7 @@ fn finish_path(stroke: Option<&Stroke>, fill: Option<&Fill>, content: &mut Conte
 }
 
 /// Set a fill and stroke opacity.
-fn set_opacity_gs(
+pub(crate) fn set_opacity_gs(
     chunk: &mut Chunk,
     content: &mut Content,
     ctx: &mut Context,
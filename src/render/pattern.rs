@@ -8,6 +8,8 @@ use super::group;
 use crate::util::context::Context;
 use crate::util::helper::TransformExt;
 use crate::util::resources::ResourceContainer;
+use crate::util::tags::{self, StreamTags};
+use crate::Result;
 
 /// Turn a pattern into a PDF tiling pattern.
 pub fn create(
@@ -16,9 +18,11 @@ pub fn create(
     ctx: &mut Context,
     matrix: Transform,
     initial_opacity: Option<Opacity>,
-) -> Ref {
+) -> Result<Ref> {
     let pattern_ref = ctx.alloc_ref();
     let mut rc = ResourceContainer::new();
+    // The content of patterns can't be part of the structure tree.
+    rc.tags = ctx.options.tagged.then(|| StreamTags::new(Some(pattern_ref)));
 
     let pattern_rect = pattern.rect();
 
@@ -36,15 +40,19 @@ pub fn create(
         content.transform(view_box_transform.to_pdf_transform());
     }
 
-    group::render(
-        pattern.root(),
-        chunk,
-        &mut content,
-        ctx,
-        Transform::default(),
-        initial_opacity,
-        &mut rc,
-    );
+    tags::render_artifact(&mut content, &mut rc, |content, rc| {
+        ctx.with_root(None, None, |ctx| {
+            group::render(
+                pattern.root(),
+                chunk,
+                content,
+                ctx,
+                Transform::default(),
+                initial_opacity,
+                rc,
+            )
+        })
+    })?;
 
     content.restore_state();
 
@@ -71,5 +79,5 @@ pub fn create(
         .x_step(final_bbox.x2 - final_bbox.x1)
         .y_step(final_bbox.y2 - final_bbox.y1);
 
-    pattern_ref
+    Ok(pattern_ref)
 }
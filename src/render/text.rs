@@ -1,21 +1,33 @@
+use super::Render;
 use crate::render::path;
 use crate::util::allocate::RefAllocator;
+use crate::util::cff;
 use crate::util::context::Context;
-use crate::util::helper::{deflate, TransformExt};
+use crate::util::font_cache::CachedFace;
+use crate::util::helper::{deflate, hash128, NameExt, RectExt, TransformExt};
 use crate::util::resources::ResourceContainer;
+use crate::{ConversionError, Result};
 use pdf_writer::types::{
-    CidFontType, FontFlags, SystemInfo, TextRenderingMode, UnicodeCmap,
+    CidFontType, ColorSpaceOperand, StructRole, SystemInfo, TextRenderingMode,
+    UnicodeCmap,
 };
-use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref, Str};
-use siphasher::sip128::{Hasher128, SipHasher13};
+use pdf_writer::{Chunk, Content, Filter, Finish, Name, Ref, Str, TextStr};
 use std::collections::{BTreeMap, HashMap};
-use std::hash::Hash;
-use ttf_parser::{name_id, Face, GlyphId, PlatformId, Tag};
-use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
-use usvg::{Fill, Group, ImageKind, Node, PaintOrder, Stroke, Transform, Visibility};
+use std::fmt::Write;
+use std::sync::Arc;
+use ttf_parser::svg::SvgDocument;
+use ttf_parser::{
+    Face, GlyphId, OutlineBuilder, PlatformId, RasterGlyphImage, RasterImageFormat,
+    RgbaColor, Tag,
+};
+use unicode_bidi::{bidi_class, BidiClass};
+use usvg::fontdb::{self, ID};
+use usvg::layout::{PositionedGlyph, Span};
+use usvg::{
+    Fill, Group, ImageKind, Node, PaintOrder, Stroke, Transform, Visibility, WritingMode,
+};
 
 const CFF: Tag = Tag::from_bytes(b"CFF ");
-const CFF2: Tag = Tag::from_bytes(b"CFF2");
 const CMAP_NAME: Name = Name(b"Custom");
 const SYSTEM_INFO: SystemInfo = SystemInfo {
     registry: Str(b"Adobe"),
@@ -24,44 +36,66 @@ const SYSTEM_INFO: SystemInfo = SystemInfo {
 };
 
 /// Write all font objects into the chunk.
-pub fn write_font(chunk: &mut Chunk, alloc: &mut RefAllocator, font: &mut Font) {
-    // We've already parsed all fonts when creating the font objects, so each font
-    // should be valid.
-    let ttf = Face::parse(&font.face_data, font.face_index).unwrap();
-    let units_per_em = ttf.units_per_em();
+pub fn write_font(chunk: &mut Chunk, ctx: &mut Context, font: &Font) {
+    let ttf = font.face.ttf();
+
+    let outline_glyphs: Vec<_> = font
+        .glyph_set
+        .keys()
+        .copied()
+        .filter(|g| font.color_glyphs.binary_search(g).is_err())
+        .collect();
+
+    if !outline_glyphs.is_empty() {
+        write_cid_font(chunk, &mut ctx.ref_allocator, &ttf, font, &outline_glyphs);
+    }
+
+    for (glyphs, &reference) in font.color_glyphs.chunks(256).zip(&font.type3_refs) {
+        write_type3_font(chunk, ctx, &ttf, font, glyphs, reference);
+    }
+}
+
+/// Write a CID font with the glyphs that are drawn from the font's outlines.
+fn write_cid_font(
+    chunk: &mut Chunk,
+    alloc: &mut RefAllocator,
+    ttf: &Face,
+    font: &Font,
+    glyphs: &[u16],
+) {
+    let units_per_em = font.face.units_per_em;
 
-    let type0_ref = font.reference;
     let cid_ref = alloc.alloc_ref();
     let descriptor_ref = alloc.alloc_ref();
     let cmap_ref = alloc.alloc_ref();
     let data_ref = alloc.alloc_ref();
 
-    let glyph_set = &mut font.glyph_set;
-
-    // Do we have a TrueType or CFF font?
-    //
-    // FIXME: CFF2 must be handled differently and requires PDF 2.0
-    // (or we have to convert it to CFF).
-    let is_cff = ttf
-        .raw_face()
-        .table(CFF)
-        .or_else(|| ttf.raw_face().table(CFF2))
-        .is_some();
+    let glyph_set = &font.glyph_set;
 
-    let postscript_name = find_name(&ttf, name_id::POST_SCRIPT_NAME)
-        .unwrap_or_else(|| "unknown".to_string());
+    // Do we have a TrueType or CFF font? CFF2 fonts can only be embedded
+    // in PDF 2.0, so we convert them into CFF fonts.
+    let face = &font.face;
+    let is_cff = face.is_cff || face.is_cff2;
+    let postscript_name = &face.postscript_name;
 
     let subset_tag = subset_tag(glyph_set);
     let base_font = format!("{subset_tag}+{postscript_name}");
-    let base_font_type0 =
-        if is_cff { format!("{base_font}-Identity-H") } else { base_font.clone() };
 
-    chunk
-        .type0_font(type0_ref)
-        .base_font(Name(base_font_type0.as_bytes()))
-        .encoding_predefined(Name(b"Identity-H"))
-        .descendant_font(cid_ref)
-        .to_unicode(cmap_ref);
+    // Glyphs in vertical text are shown with a second Type 0 font that shares
+    // the CID font, but writes vertically.
+    let type0_fonts = std::iter::once((font.reference, "Identity-H"))
+        .chain(font.vertical_reference.map(|r| (r, "Identity-V")));
+    for (type0_ref, encoding) in type0_fonts {
+        let base_font_type0 =
+            if is_cff { format!("{base_font}-{encoding}") } else { base_font.clone() };
+
+        chunk
+            .type0_font(type0_ref)
+            .base_font(Name(base_font_type0.as_bytes()))
+            .encoding_predefined(Name(encoding.as_bytes()))
+            .descendant_font(cid_ref)
+            .to_unicode(cmap_ref);
+    }
 
     // Write the CID font referencing the font descriptor.
     let mut cid = chunk.cid_font(cid_ref);
@@ -75,10 +109,10 @@ pub fn write_font(chunk: &mut Chunk, alloc: &mut RefAllocator, font: &mut Font)
     }
 
     let mut widths = vec![];
-    for gid in std::iter::once(0).chain(glyph_set.keys().copied()) {
+    for gid in std::iter::once(0).chain(glyphs.iter().copied()) {
         let width = ttf.glyph_hor_advance(GlyphId(gid)).unwrap_or(0);
         let units = (width as f64 / units_per_em as f64) * 1000.0;
-        let cid = glyph_cid(&ttf, gid);
+        let cid = glyph_cid(ttf, gid);
         if usize::from(cid) >= widths.len() {
             widths.resize(usize::from(cid) + 1, 0.0);
             widths[usize::from(cid)] = units as f32;
@@ -98,40 +132,33 @@ pub fn write_font(chunk: &mut Chunk, alloc: &mut RefAllocator, font: &mut Font)
     }
 
     width_writer.finish();
-    cid.finish();
 
-    let mut flags = FontFlags::empty();
-    flags.set(FontFlags::SERIF, postscript_name.contains("Serif"));
-    flags.set(FontFlags::FIXED_PITCH, ttf.is_monospaced());
-    flags.set(FontFlags::ITALIC, ttf.is_italic());
-    flags.insert(FontFlags::SYMBOLIC);
-    flags.insert(FontFlags::SMALL_CAP);
-
-    let global_bbox = ttf.global_bounding_box();
-    let bbox = pdf_writer::Rect::new(
-        (global_bbox.x_min as f32 / units_per_em as f32) * 1000.0,
-        (global_bbox.y_min as f32 / units_per_em as f32) * 1000.0,
-        (global_bbox.x_max as f32 / units_per_em as f32) * 1000.0,
-        (global_bbox.y_max as f32 / units_per_em as f32) * 1000.0,
-    );
+    // Write the vertical metrics of all glyphs in thousandths of an em: The
+    // vertical advance, which is negative since it moves down, and the position of
+    // the vertical origin relative to the horizontal one.
+    if font.vertical_reference.is_some() {
+        let scale = 1000.0 / units_per_em as f32;
+        let mut metrics = cid.insert(Name(b"W2")).array();
+        for &gid in glyphs {
+            let (advance, x, y) = vertical_metrics(ttf, GlyphId(gid));
+            metrics.item(i32::from(glyph_cid(ttf, gid)));
+            metrics.push().array().items([-advance, x, y].map(|v| v * scale));
+        }
+    }
 
-    let italic_angle = ttf.italic_angle().unwrap_or(0.0);
-    let ascender = ttf.typographic_ascender().unwrap_or(ttf.ascender());
-    let descender = ttf.typographic_descender().unwrap_or(ttf.descender());
-    let cap_height = ttf.capital_height().filter(|&h| h > 0).unwrap_or(ascender);
-    let stem_v = 10.0 + 0.244 * (f32::from(ttf.weight().to_number()) - 50.0);
+    cid.finish();
 
     // Write the font descriptor (contains metrics about the font).
     let mut font_descriptor = chunk.font_descriptor(descriptor_ref);
     font_descriptor
         .name(Name(base_font.as_bytes()))
-        .flags(flags)
-        .bbox(bbox)
-        .italic_angle(italic_angle)
-        .ascent(ascender as f32)
-        .descent(descender as f32)
-        .cap_height(cap_height as f32)
-        .stem_v(stem_v);
+        .flags(face.flags)
+        .bbox(face.bbox)
+        .italic_angle(face.italic_angle)
+        .ascent(face.ascender)
+        .descent(face.descender)
+        .cap_height(face.cap_height)
+        .stem_v(face.stem_v);
 
     if is_cff {
         font_descriptor.font_file3(data_ref);
@@ -141,12 +168,19 @@ pub fn write_font(chunk: &mut Chunk, alloc: &mut RefAllocator, font: &mut Font)
 
     font_descriptor.finish();
 
-    let cmap = create_cmap(&ttf, glyph_set);
+    let cmap = create_cmap(ttf, glyph_set, glyphs);
     chunk.cmap(cmap_ref, &cmap.finish());
 
-    // Subset and write the font's bytes.
-    let glyphs: Vec<_> = glyph_set.keys().copied().collect();
-    let data = subset_font(&font.face_data, font.face_index, &glyphs);
+    // Subset and write the font's bytes. usvg never applies variation
+    // coordinates, so text is always laid out with the default instance of
+    // variable fonts. That is also the only instance that we embed, as a static
+    // font: The subset of a TrueType font drops the variation tables and CFF2
+    // fonts are converted with the default variation coordinates.
+    let data = if face.is_cff2 {
+        deflate(&cff::convert(ttf, postscript_name, glyphs))
+    } else {
+        subset_font(face.data(), face.index, glyphs)
+    };
 
     let mut stream = chunk.stream(data_ref, &data);
     stream.filter(Filter::FlateDecode);
@@ -157,34 +191,31 @@ pub fn write_font(chunk: &mut Chunk, alloc: &mut RefAllocator, font: &mut Font)
     stream.finish();
 }
 
-/// Create a /ToUnicode CMap.
-fn create_cmap(ttf: &Face, glyph_set: &mut BTreeMap<u16, String>) -> UnicodeCmap {
+/// Determine the text that each glyph of the font is mapped to in its ToUnicode
+/// CMap. Must be called once all glyphs have been collected.
+pub fn resolve_texts(face: &CachedFace, glyph_set: &mut BTreeMap<u16, String>) {
     // For glyphs that have codepoints mapping to them in the font's cmap table,
     // we prefer them over pre-existing text mappings from the document. Only
     // things that don't have a corresponding codepoint (or only a private-use
     // one) like the "Th" in Linux Libertine get the text of their first
     // occurrences in the document instead.
-    for subtable in ttf.tables().cmap.into_iter().flat_map(|table| table.subtables) {
-        if !subtable.is_unicode() {
-            continue;
+    for (g, text) in glyph_set.iter_mut() {
+        if let Some(&c) = face.codepoints.get(g) {
+            *text = c.into();
         }
-
-        subtable.codepoints(|n| {
-            let Some(c) = std::char::from_u32(n) else { return };
-            if c.general_category() == GeneralCategory::PrivateUse {
-                return;
-            }
-
-            let Some(GlyphId(g)) = ttf.glyph_index(c) else { return };
-            if glyph_set.contains_key(&g) {
-                glyph_set.insert(g, c.into());
-            }
-        });
     }
+}
 
+/// Create a /ToUnicode CMap.
+fn create_cmap(
+    ttf: &Face,
+    glyph_set: &BTreeMap<u16, String>,
+    glyphs: &[u16],
+) -> UnicodeCmap {
     // Produce a reverse mapping from glyphs' CIDs to unicode strings.
     let mut cmap = UnicodeCmap::new(CMAP_NAME, SYSTEM_INFO);
-    for (&g, text) in glyph_set.iter() {
+    for &g in glyphs {
+        let text = &glyph_set[&g];
         if !text.is_empty() {
             cmap.pair_with_multiple(glyph_cid(ttf, g), text.chars());
         }
@@ -194,20 +225,410 @@ fn create_cmap(ttf: &Face, glyph_set: &mut BTreeMap<u16, String>) -> UnicodeCmap
 }
 
 fn subset_font(font_data: &[u8], index: u32, glyphs: &[u16]) -> Vec<u8> {
-    let data = font_data;
     let profile = subsetter::Profile::pdf(glyphs);
-    let subsetted = subsetter::subset(data, index, profile);
-    let mut data = subsetted.as_deref().unwrap_or(data);
+    let subsetted = subsetter::subset(font_data, index, profile)
+        .ok()
+        // If the subset is broken, we embed the whole font instead.
+        .filter(|data| ttf_parser::RawFace::parse(data, 0).is_ok());
+    let (mut data, index) = match &subsetted {
+        Some(subsetted) => (subsetted.as_slice(), 0),
+        None => (font_data, index),
+    };
 
     // Extract the standalone CFF font program if applicable.
-    let face = ttf_parser::RawFace::parse(data, 0).unwrap();
-    if let Some(cff) = face.table(CFF) {
+    if let Some(cff) = ttf_parser::RawFace::parse(data, index)
+        .ok()
+        .and_then(|face| face.table(CFF))
+    {
         data = cff;
     }
 
     deflate(data)
 }
 
+/// Write a Type 3 font with up to 256 color glyphs, which are drawn from the
+/// font's COLR layers, SVG documents or bitmaps instead of its outlines.
+fn write_type3_font(
+    chunk: &mut Chunk,
+    ctx: &mut Context,
+    ttf: &Face,
+    font: &Font,
+    glyphs: &[u16],
+    reference: Ref,
+) {
+    let cmap_ref = ctx.alloc_ref();
+    let mut rc = ResourceContainer::new();
+
+    let mut procs = vec![];
+    let mut widths = vec![];
+    for &gid in glyphs {
+        let width = f32::from(ttf.glyph_hor_advance(GlyphId(gid)).unwrap_or(0));
+        let mut content = Content::new();
+        content.start_color_glyph(width);
+        draw_color_glyph(ttf, GlyphId(gid), chunk, &mut content, ctx, &mut rc);
+
+        let proc_ref = ctx.alloc_ref();
+        let stream = ctx.finish_content(content);
+        let mut proc = chunk.stream(proc_ref, &stream);
+        if ctx.options.compress {
+            proc.filter(Filter::FlateDecode);
+        }
+
+        proc.finish();
+        procs.push((format!("g{gid}"), proc_ref));
+        widths.push(width);
+    }
+
+    // The glyph space is the one of the font's outlines.
+    let scale = 1.0 / f32::from(ttf.units_per_em());
+
+    // Bitmaps and SVG documents may exceed the font's bounding box, so we don't
+    // give one.
+    let bbox = pdf_writer::Rect::new(0.0, 0.0, 0.0, 0.0);
+
+    // Tagged PDFs require a font descriptor for Type 3 fonts. Its metrics are in
+    // glyph space as well.
+    let descriptor_ref = ctx.options.tagged.then(|| ctx.alloc_ref());
+    if let Some(descriptor_ref) = descriptor_ref {
+        let face = &font.face;
+        chunk
+            .font_descriptor(descriptor_ref)
+            .name(Name(face.postscript_name.as_bytes()))
+            .flags(face.flags)
+            .bbox(bbox)
+            .italic_angle(face.italic_angle)
+            .ascent(face.ascender)
+            .descent(face.descender)
+            .cap_height(face.cap_height)
+            .stem_v(face.stem_v);
+    }
+
+    let mut type3 = chunk.type3_font(reference);
+    type3.bbox(bbox);
+    type3.matrix([scale, 0.0, 0.0, scale, 0.0, 0.0]);
+    type3.first_char(0);
+    type3.last_char((glyphs.len() - 1) as u8);
+    type3.widths(widths);
+    type3.to_unicode(cmap_ref);
+    if let Some(descriptor_ref) = descriptor_ref {
+        type3.font_descriptor(descriptor_ref);
+    }
+    type3
+        .encoding_custom()
+        .differences()
+        .consecutive(0, procs.iter().map(|(name, _)| Name(name.as_bytes())));
+
+    let mut char_procs = type3.char_procs();
+    for (name, proc_ref) in &procs {
+        char_procs.pair(Name(name.as_bytes()), *proc_ref);
+    }
+
+    char_procs.finish();
+
+    let mut resources = type3.resources();
+    rc.finish(&mut resources);
+    resources.finish();
+    type3.finish();
+
+    let texts = glyphs.iter().map(|g| font.glyph_set[g].as_str());
+    chunk.cmap(cmap_ref, &create_type3_cmap(texts));
+}
+
+/// Create a /ToUnicode CMap for the single-byte character codes of a Type 3 font,
+/// which [`UnicodeCmap`] cannot produce.
+fn create_type3_cmap<'a>(texts: impl Iterator<Item = &'a str>) -> Vec<u8> {
+    let mut cmap = String::new();
+    cmap.push_str("/CIDInit /ProcSet findresource begin\n");
+    cmap.push_str("12 dict begin\n");
+    cmap.push_str("begincmap\n");
+    cmap.push_str(
+        "/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n",
+    );
+    cmap.push_str("/CMapName /Adobe-Identity-UCS def\n");
+    cmap.push_str("/CMapType 2 def\n");
+    cmap.push_str("1 begincodespacerange\n<00> <ff>\nendcodespacerange\n");
+
+    let mappings: Vec<_> =
+        texts.enumerate().filter(|(_, text)| !text.is_empty()).collect();
+
+    // At most 100 lines per range.
+    for range in mappings.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", range.len());
+        for (code, text) in range {
+            let _ = write!(cmap, "<{code:02x}> <");
+            for unit in text.encode_utf16() {
+                let _ = write!(cmap, "{unit:04x}");
+            }
+            cmap.push_str(">\n");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str("endcmap\n");
+    cmap.push_str("CMapName currentdict /CMap defineresource pop\n");
+    cmap.push_str("end\n");
+    cmap.push_str("end\n");
+    cmap.into_bytes()
+}
+
+/// Whether a glyph has a color representation that we can draw.
+fn is_color_glyph(ttf: &Face, id: GlyphId) -> bool {
+    ttf.is_color_glyph(id)
+        || ttf.glyph_svg_image(id).is_some()
+        || bitmap_glyph(ttf, id).is_some()
+}
+
+/// Get the bitmap of a glyph with the highest resolution, if it can be embedded.
+fn bitmap_glyph<'a>(ttf: &'a Face, id: GlyphId) -> Option<RasterGlyphImage<'a>> {
+    if !cfg!(feature = "image") {
+        return None;
+    }
+
+    ttf.glyph_raster_image(id, u16::MAX)
+        .filter(|image| image.format == RasterImageFormat::PNG)
+}
+
+/// Draw a color glyph into the procedure of a Type 3 font. COLR layers are
+/// preferred over SVG documents, which are preferred over bitmaps.
+fn draw_color_glyph(
+    ttf: &Face,
+    id: GlyphId,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) {
+    let result = if ttf.is_color_glyph(id) {
+        let mut painter = ColrPainter { ttf, chunk, content, ctx, rc, outline: None };
+        ttf.paint_color_glyph(id, 0, &mut painter);
+        Ok(())
+    } else if let Some(document) = ttf.glyph_svg_image(id) {
+        draw_svg_glyph(document, id, chunk, content, ctx, rc)
+    } else if let Some(image) = bitmap_glyph(ttf, id) {
+        draw_bitmap_glyph(ttf, image, chunk, content, ctx, rc)
+    } else {
+        Ok(())
+    };
+
+    if let Err(err) = result {
+        log::warn!("Failed to convert color glyph {}: {err}. Skipping.", id.0);
+    }
+}
+
+/// Draw the node of a glyph in an SVG document. The document's coordinates are
+/// font units with the y-axis pointing down.
+fn draw_svg_glyph(
+    document: SvgDocument,
+    id: GlyphId,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    let Ok(tree) = usvg::Tree::from_data(
+        document.data,
+        &usvg::Options::default(),
+        &fontdb::Database::new(),
+    ) else {
+        log::warn!("Failed to parse SVG document of glyph {}. Skipping.", id.0);
+        return Ok(());
+    };
+
+    // A document can contain multiple glyphs, which are identified by their ids.
+    let root = Node::Group(Box::new(tree.root().clone()));
+    let node = if document.start_glyph_id == document.end_glyph_id {
+        &root
+    } else {
+        match tree.node_by_id(&format!("glyph{}", id.0)) {
+            Some(node) => node,
+            None => return Ok(()),
+        }
+    };
+
+    let Some(bbox) = node.abs_layer_bounding_box() else { return Ok(()) };
+
+    // The node is rendered into an XObject so that patterns and gradients are
+    // relative to the glyph.
+    let parent_transform = match node {
+        Node::Group(group) => group
+            .abs_transform()
+            .pre_concat(group.transform().invert().unwrap_or_default()),
+        node => node.abs_transform(),
+    };
+
+    let mut x_rc = ResourceContainer::new();
+    let mut x_content = Content::new();
+    x_content.transform(parent_transform.to_pdf_transform());
+    ctx.with_root(None, None, |ctx| {
+        node.render(chunk, &mut x_content, ctx, parent_transform, &mut x_rc)
+    })?;
+
+    let x_ref = ctx.alloc_ref();
+    let stream = ctx.finish_content(x_content);
+    let mut x_object = chunk.form_xobject(x_ref, &stream);
+    x_object.bbox(bbox.to_pdf_rect());
+    if ctx.options.compress {
+        x_object.filter(Filter::FlateDecode);
+    }
+
+    let mut resources = x_object.resources();
+    x_rc.finish(&mut resources);
+    resources.finish();
+    x_object.finish();
+
+    content.save_state();
+    content.transform([1.0, 0.0, 0.0, -1.0, 0.0, 0.0]);
+    content.x_object(rc.add_x_object(x_ref).to_pdf_name());
+    content.restore_state();
+
+    Ok(())
+}
+
+/// Draw the bitmap of a glyph, which is scaled from its strike's pixels per em
+/// to font units.
+#[cfg(feature = "image")]
+fn draw_bitmap_glyph(
+    ttf: &Face,
+    image: RasterGlyphImage,
+    chunk: &mut Chunk,
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+) -> Result<()> {
+    let kind = ImageKind::PNG(Arc::new(image.data.to_vec()));
+    let interpolate = (!ctx.options.pdfa).then_some(true);
+    let (image_ref, _) = super::image::create_image(&kind, interpolate, chunk, ctx)?;
+
+    let scale = f32::from(ttf.units_per_em()) / f32::from(image.pixels_per_em.max(1));
+    content.save_state();
+    content.transform([
+        f32::from(image.width) * scale,
+        0.0,
+        0.0,
+        f32::from(image.height) * scale,
+        f32::from(image.x) * scale,
+        f32::from(image.y) * scale,
+    ]);
+    content.x_object(rc.add_x_object(image_ref).to_pdf_name());
+    content.restore_state();
+
+    Ok(())
+}
+
+#[cfg(not(feature = "image"))]
+fn draw_bitmap_glyph(
+    _: &Face,
+    _: RasterGlyphImage,
+    _: &mut Chunk,
+    _: &mut Content,
+    _: &mut Context,
+    _: &mut ResourceContainer,
+) -> Result<()> {
+    Ok(())
+}
+
+/// Paints the layers of a COLR glyph into a content stream.
+struct ColrPainter<'a, 'b> {
+    ttf: &'a Face<'b>,
+    chunk: &'a mut Chunk,
+    content: &'a mut Content,
+    ctx: &'a mut Context,
+    rc: &'a mut ResourceContainer,
+    outline: Option<GlyphId>,
+}
+
+impl ColrPainter<'_, '_> {
+    /// Fill the current outline with a color or, if there is none, with the
+    /// current fill color of the text.
+    fn fill(&mut self, color: Option<RgbaColor>) {
+        let Some(outline) = self.outline else { return };
+
+        self.content.save_state();
+
+        if let Some(color) = color {
+            if color.alpha != u8::MAX {
+                let gs_ref = self.ctx.alloc_ref();
+                self.chunk
+                    .ext_graphics(gs_ref)
+                    .non_stroking_alpha(f32::from(color.alpha) / 255.0);
+                let gs_name = self.rc.add_graphics_state(gs_ref);
+                self.content.set_parameters(gs_name.to_pdf_name());
+            }
+
+            let color_space_name = self.rc.add_color_space(self.ctx.color_space_ref());
+            self.content.set_fill_color_space(ColorSpaceOperand::Named(
+                color_space_name.to_pdf_name(),
+            ));
+            let rgb = [color.red, color.green, color.blue].map(|c| f32::from(c) / 255.0);
+            self.content.set_fill_color(self.ctx.convert_color(rgb));
+        }
+
+        let mut builder = OutlineWriter { content: &mut *self.content, last: (0.0, 0.0) };
+        if self.ttf.outline_glyph(outline, &mut builder).is_some() {
+            self.content.fill_nonzero();
+        } else {
+            self.content.end_path();
+        }
+
+        self.content.restore_state();
+    }
+}
+
+impl ttf_parser::colr::Painter for ColrPainter<'_, '_> {
+    fn outline(&mut self, glyph_id: GlyphId) {
+        self.outline = Some(glyph_id);
+    }
+
+    fn paint_foreground(&mut self) {
+        self.fill(None);
+    }
+
+    fn paint_color(&mut self, color: RgbaColor) {
+        self.fill(Some(color));
+    }
+}
+
+/// Writes the outline of a glyph as a path into a content stream.
+struct OutlineWriter<'a> {
+    content: &'a mut Content,
+    last: (f32, f32),
+}
+
+impl OutlineBuilder for OutlineWriter<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.content.move_to(x, y);
+        self.last = (x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.content.line_to(x, y);
+        self.last = (x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        // PDF only has cubic curves, so we elevate the quadratic one.
+        let (x0, y0) = self.last;
+        self.content.cubic_to(
+            x0 + 2.0 / 3.0 * (x1 - x0),
+            y0 + 2.0 / 3.0 * (y1 - y0),
+            x + 2.0 / 3.0 * (x1 - x),
+            y + 2.0 / 3.0 * (y1 - y),
+            x,
+            y,
+        );
+        self.last = (x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.content.cubic_to(x1, y1, x2, y2, x, y);
+        self.last = (x, y);
+    }
+
+    fn close(&mut self) {
+        self.content.close_path();
+    }
+}
+
 /// Render some text into a content stream.
 pub fn render(
     text: &usvg::Text,
@@ -216,68 +637,180 @@ pub fn render(
     ctx: &mut Context,
     rc: &mut ResourceContainer,
     accumulated_transform: Transform,
-) {
+) -> Result<()> {
+    // The fonts are only borrowed from the context while the glyphs are looked up,
+    // since painting the text may render other text, e.g. in a pattern.
     let mut font_names = HashMap::new();
-
-    // TODO: Don't clone here...
-    let fonts = ctx.fonts.clone();
+    let mut faces = HashMap::new();
+    let mut shown_glyphs = vec![];
 
     for span in text.layouted() {
+        let mut shown = vec![];
         for glyph in &span.positioned_glyphs {
-            let Some(font) = ctx.font_ref(glyph.font) else { continue };
+            let Some(font) = ctx.font_ref(glyph.font) else {
+                shown.push(None);
+                continue;
+            };
+
+            // The faces are needed for the advances of the glyphs.
+            let ttf = faces.entry(glyph.font).or_insert_with(|| font.face.ttf());
+            let shown_glyph = ShownGlyph::new(text, glyph, font, ttf);
             font_names
-                .entry(font.reference)
-                .or_insert_with(|| rc.add_font(font.reference));
+                .entry(shown_glyph.reference)
+                .or_insert_with(|| rc.add_font(shown_glyph.reference));
+            shown.push(Some(shown_glyph));
         }
+        shown_glyphs.push(shown);
     }
 
-    for span in text.layouted() {
+    // In tagged documents, the text becomes a paragraph with a span for each of
+    // its spans.
+    let paragraph = rc
+        .tags
+        .is_some()
+        .then(|| ctx.tags.push(StructRole::P, None, None, None));
+
+    for (span, shown_glyphs) in text.layouted().iter().zip(&shown_glyphs) {
         if span.visibility != Visibility::Visible {
             continue;
         }
 
-        let operation = |content: &mut Content| {
-            for glyph in &span.positioned_glyphs {
-                let Some(font) = fonts.get(&glyph.font).and_then(|f| f.as_ref()) else {
-                    continue;
-                };
+        if let (Some(paragraph), Some(stream)) = (paragraph, rc.tags.as_mut()) {
+            let element = ctx.tags.push(StructRole::Span, Some(paragraph), None, None);
+            ctx.tags.begin_marked_content(content, stream, element, Name(b"Span"));
+        }
+
+        let font_size = span.font_size.get();
+        let actual_texts = find_actual_texts(span, &ctx.fonts);
 
-                let name = font_names.get(&font.reference).unwrap();
+        // Type 3 fonts ignore the text rendering mode, so color glyphs are only
+        // shown once and only if the text is visible at all.
+        let operation = |content: &mut Content, with_color_glyphs: bool| {
+            let mut run = TextRun::default();
+            // The current text line matrix, whether it is vertical, and the position
+            // of the pen on it.
+            let mut line: Option<(Transform, bool)> = None;
+            let mut pen = 0.0;
+            // The index of the glyph after the current run with an actual text.
+            let mut actual_text_end = None;
 
-                let gid = glyph.glyph_id.0;
+            content.begin_text();
+            for (i, glyph) in span.positioned_glyphs.iter().enumerate() {
+                if actual_text_end == Some(i) {
+                    run.write(content);
+                    content.end_marked_content();
+                    actual_text_end = None;
+                }
+
+                if let Some((end, actual_text)) = actual_texts.get(&i) {
+                    run.write(content);
+                    content
+                        .begin_marked_content_with_properties(Name(b"Span"))
+                        .properties()
+                        .actual_text(TextStr(actual_text));
+                    actual_text_end = Some(*end);
+                }
+
+                let Some(shown) = &shown_glyphs[i] else { continue };
+                if shown.type3_code.is_some() && !with_color_glyphs {
+                    continue;
+                }
+
+                let ShownGlyph { reference, vertical, upem, .. } = *shown;
                 let ts = glyph
                     .transform
-                    .pre_scale(font.units_per_em as f32, font.units_per_em as f32)
+                    .pre_scale(upem, upem)
                     // The glyphs in usvg are already scaled according the font size, but
                     // we want to leverage the native PDF font size feature instead, so we downscale
                     // it to a font size of 1.
-                    .pre_scale(1.0 / span.font_size.get(), 1.0 / span.font_size.get());
-                content.save_state();
-                content.begin_text();
-                content.set_text_matrix(ts.to_pdf_transform());
-                content.set_font(Name(name.as_bytes()), span.font_size.get());
-                content.show(Str(&[(gid >> 8) as u8, (gid & 0xff) as u8]));
-                content.end_text();
-                content.restore_state();
+                    .pre_scale(1.0 / font_size, 1.0 / font_size);
+
+                // In vertical text, glyphs are positioned by their vertical origin
+                // and the pen moves down.
+                let (ts, advance) = if vertical {
+                    let (x, y) = shown.origin;
+                    let scale = font_size / upem;
+                    (ts.pre_translate(x * scale, y * scale), -shown.advance * scale)
+                } else {
+                    (ts, shown.advance / upem * font_size)
+                };
+
+                // The offset of the glyph along the current line, if it is on it.
+                let offset =
+                    line.filter(|&(_, v)| v == vertical).and_then(|(line, _)| {
+                        let relative = line.invert()?.pre_concat(ts);
+                        let (along, across) = if vertical {
+                            (relative.ty, relative.tx)
+                        } else {
+                            (relative.tx, relative.ty)
+                        };
+                        let on_line = (relative.sx - 1.0).abs() < 1e-3
+                            && relative.ky.abs() < 1e-3
+                            && relative.kx.abs() < 1e-3
+                            && (relative.sy - 1.0).abs() < 1e-3
+                            && across.abs() < 1e-3 * font_size;
+                        on_line.then_some(along)
+                    });
+
+                let offset = match offset {
+                    Some(offset) => offset,
+                    None => {
+                        run.write(content);
+                        content.set_text_matrix(ts.to_pdf_transform());
+                        line = Some((ts, vertical));
+                        pen = 0.0;
+                        0.0
+                    }
+                };
+
+                if run.font != Some(reference) {
+                    run.write(content);
+                    let name = font_names.get(&reference).unwrap();
+                    content.set_font(Name(name.as_bytes()), font_size);
+                    run.font = Some(reference);
+                }
+
+                // Move the pen from the end of the previous glyph to the start of
+                // this one. The adjustment is in thousandths of text space units
+                // and positive values move to the left or, in vertical text, down.
+                // It is rounded, since the pen is moved to the exact offset for each
+                // glyph anyway.
+                let adjustment = ((pen - offset) / font_size * 100_000.0).round() / 100.0;
+                if adjustment != 0.0 {
+                    run.adjust(adjustment);
+                }
+
+                match shown.type3_code {
+                    Some(code) => run.show(&[code]),
+                    None => run.show(&glyph.glyph_id.0.to_be_bytes()),
+                }
+
+                pen = offset + advance;
+            }
+
+            run.write(content);
+            if actual_text_end.is_some() {
+                content.end_marked_content();
             }
+            content.end_text();
         };
 
         let stroke_operation = |content: &mut Content, _: &Stroke| {
             content.set_text_rendering_mode(TextRenderingMode::Stroke);
-            operation(content);
+            operation(content, span.fill.is_none());
         };
 
         let fill_operation = |content: &mut Content, _: &Fill| {
             content.set_text_rendering_mode(TextRenderingMode::Fill);
-            operation(content);
+            operation(content, true);
         };
 
         if let Some(overline) = &span.overline {
-            path::render(overline, chunk, content, ctx, rc, accumulated_transform);
+            path::render(overline, chunk, content, ctx, rc, accumulated_transform)?;
         }
 
         if let Some(underline) = &span.underline {
-            path::render(underline, chunk, content, ctx, rc, accumulated_transform);
+            path::render(underline, chunk, content, ctx, rc, accumulated_transform)?;
         }
 
         content.save_state();
@@ -293,7 +826,7 @@ pub fn render(
                         fill_operation,
                         accumulated_transform,
                         text.bounding_box(),
-                    );
+                    )?;
                     path::stroke(
                         stroke,
                         chunk,
@@ -303,7 +836,7 @@ pub fn render(
                         stroke_operation,
                         accumulated_transform,
                         text.bounding_box(),
-                    );
+                    )?;
                 }
                 PaintOrder::StrokeAndFill => {
                     path::stroke(
@@ -315,7 +848,7 @@ pub fn render(
                         stroke_operation,
                         accumulated_transform,
                         text.bounding_box(),
-                    );
+                    )?;
                     path::fill(
                         fill,
                         chunk,
@@ -325,7 +858,7 @@ pub fn render(
                         fill_operation,
                         accumulated_transform,
                         text.bounding_box(),
-                    );
+                    )?;
                 }
             },
             (None, Some(stroke)) => {
@@ -338,7 +871,7 @@ pub fn render(
                     stroke_operation,
                     accumulated_transform,
                     text.bounding_box(),
-                );
+                )?;
             }
             (Some(fill), None) => {
                 path::fill(
@@ -350,24 +883,175 @@ pub fn render(
                     fill_operation,
                     accumulated_transform,
                     text.bounding_box(),
-                );
+                )?;
             }
             (None, None) => {
                 content.set_text_rendering_mode(TextRenderingMode::Invisible);
-                operation(content);
+                operation(content, false);
             }
         };
 
         content.restore_state();
 
         if let Some(line_through) = &span.line_through {
-            path::render(line_through, chunk, content, ctx, rc, accumulated_transform);
+            path::render(line_through, chunk, content, ctx, rc, accumulated_transform)?;
+        }
+
+        if paragraph.is_some() {
+            content.end_marked_content();
+        }
+    }
+
+    Ok(())
+}
+
+/// How a glyph is shown with its font.
+#[derive(Copy, Clone)]
+struct ShownGlyph {
+    /// The font the glyph is shown with.
+    reference: Ref,
+    /// The code of the glyph in its Type 3 font, if it is a color glyph.
+    type3_code: Option<u8>,
+    /// Whether the glyph is shown with the vertical font.
+    vertical: bool,
+    /// The units per em of the font.
+    upem: f32,
+    /// The advance of the glyph in font units, in the direction of the text.
+    advance: f32,
+    /// The vertical origin of the glyph in font units, if it is shown vertically.
+    origin: (f32, f32),
+}
+
+impl ShownGlyph {
+    fn new(text: &usvg::Text, glyph: &PositionedGlyph, font: &Font, ttf: &Face) -> Self {
+        let id = GlyphId(glyph.glyph_id.0);
+        let type3_glyph = font.type3_glyph(id.0);
+
+        // Upright glyphs in vertical text are shown with the vertical font.
+        let vertical_reference = font
+            .vertical_reference
+            .filter(|_| type3_glyph.is_none() && is_vertical(text, glyph));
+        let reference = match type3_glyph {
+            Some((reference, _)) => reference,
+            None => vertical_reference.unwrap_or(font.reference),
+        };
+
+        let (advance, origin) = if vertical_reference.is_some() {
+            let (advance, x, y) = vertical_metrics(ttf, id);
+            (advance, (x, y))
+        } else {
+            (f32::from(ttf.glyph_hor_advance(id).unwrap_or(0)), (0.0, 0.0))
+        };
+
+        Self {
+            reference,
+            type3_code: type3_glyph.map(|(_, code)| code),
+            vertical: vertical_reference.is_some(),
+            upem: font.face.units_per_em as f32,
+            advance,
+            origin,
+        }
+    }
+}
+
+/// Find the runs of glyphs in a span whose text differs from the text that the
+/// ToUnicode CMaps map them to, for example because a glyph is used for different
+/// texts or a cluster consists of several glyphs. Returns the index of the first
+/// glyph of each run together with the index after its last glyph and the text
+/// that the run represents.
+fn find_actual_texts(
+    span: &Span,
+    fonts: &HashMap<ID, Option<Font>>,
+) -> HashMap<usize, (usize, String)> {
+    let conflicts = |glyph: &PositionedGlyph| {
+        fonts.get(&glyph.font).and_then(|f| f.as_ref()).is_some_and(|font| {
+            font.glyph_set.get(&glyph.glyph_id.0) != Some(&glyph.text)
+        })
+    };
+
+    let mut actual_texts = HashMap::new();
+    let glyphs = &span.positioned_glyphs;
+    let mut start = 0;
+    while start < glyphs.len() {
+        if !conflicts(&glyphs[start]) {
+            start += 1;
+            continue;
+        }
+
+        let end = glyphs[start..]
+            .iter()
+            .position(|glyph| !conflicts(glyph))
+            .map_or(glyphs.len(), |len| start + len);
+
+        // The glyphs are in visual order, but each of them has the text of its
+        // cluster in logical order.
+        let mut texts: Vec<_> =
+            glyphs[start..end].iter().map(|g| g.text.as_str()).collect();
+        let rtl =
+            texts.iter().flat_map(|text| text.chars()).find_map(|c| {
+                match bidi_class(c) {
+                    BidiClass::L => Some(false),
+                    BidiClass::R | BidiClass::AL => Some(true),
+                    _ => None,
+                }
+            });
+        if rtl == Some(true) {
+            texts.reverse();
+        }
+
+        actual_texts.insert(start, (end, texts.concat()));
+        start = end;
+    }
+
+    actual_texts
+}
+
+/// Consecutive glyphs of one font that are shown with a single `TJ` operator.
+#[derive(Default)]
+struct TextRun {
+    /// The font that is currently selected.
+    font: Option<Ref>,
+    /// The encoded glyphs and the adjustments between them.
+    items: Vec<TextRunItem>,
+}
+
+enum TextRunItem {
+    Show(Vec<u8>),
+    Adjust(f32),
+}
+
+impl TextRun {
+    fn show(&mut self, code: &[u8]) {
+        match self.items.last_mut() {
+            Some(TextRunItem::Show(codes)) => codes.extend(code),
+            _ => self.items.push(TextRunItem::Show(code.to_vec())),
+        }
+    }
+
+    fn adjust(&mut self, amount: f32) {
+        self.items.push(TextRunItem::Adjust(amount));
+    }
+
+    /// Write the glyphs of the run, if there are any, and start a new one.
+    fn write(&mut self, content: &mut Content) {
+        if !self.items.iter().any(|item| matches!(item, TextRunItem::Show(_))) {
+            self.items.clear();
+            return;
+        }
+
+        let mut positioned = content.show_positioned();
+        let mut items = positioned.items();
+        for item in self.items.drain(..) {
+            match item {
+                TextRunItem::Show(codes) => items.show(Str(&codes)),
+                TextRunItem::Adjust(amount) => items.adjust(amount),
+            };
         }
     }
 }
 
 /// Produce a unique 6 letter tag for a glyph set.
-fn subset_tag(glyphs: &mut BTreeMap<u16, String>) -> String {
+fn subset_tag(glyphs: &BTreeMap<u16, String>) -> String {
     const LEN: usize = 6;
     const BASE: u128 = 26;
     let mut hash = hash128(&glyphs);
@@ -379,15 +1063,8 @@ fn subset_tag(glyphs: &mut BTreeMap<u16, String>) -> String {
     std::str::from_utf8(&letter).unwrap().into()
 }
 
-/// Calculate a 128-bit siphash of a value.
-pub fn hash128<T: Hash + ?Sized>(value: &T) -> u128 {
-    let mut state = SipHasher13::new();
-    value.hash(&mut state);
-    state.finish128().as_u128()
-}
-
 /// Try to find and decode the name with the given id.
-pub(super) fn find_name(ttf: &Face, name_id: u16) -> Option<String> {
+pub(crate) fn find_name(ttf: &Face, name_id: u16) -> Option<String> {
     ttf.names().into_iter().find_map(|entry| {
         if entry.name_id == name_id {
             if let Some(string) = entry.to_string() {
@@ -435,6 +1112,38 @@ fn glyph_cid(ttf: &Face, glyph_id: u16) -> u16 {
         .unwrap_or(glyph_id)
 }
 
+/// Whether a glyph is shown upright in vertical text. Glyphs that are rotated
+/// sideways are shown horizontally instead.
+fn is_vertical(text: &usvg::Text, glyph: &PositionedGlyph) -> bool {
+    let ts = glyph.transform;
+    text.writing_mode() == WritingMode::TopToBottom
+        && ts.sx > 0.0
+        && ts.kx.abs() <= 1e-4 * ts.sx
+        && ts.ky.abs() <= 1e-4 * ts.sx
+}
+
+/// Determine the vertical advance of a glyph and the position of its vertical
+/// origin relative to its horizontal one, all in font units. The origin is
+/// centered horizontally and taken from the VORG table or, failing that, from the
+/// glyph's top side bearing.
+fn vertical_metrics(ttf: &Face, id: GlyphId) -> (f32, f32, f32) {
+    let ascender = f32::from(ttf.ascender());
+    let advance = ttf
+        .glyph_ver_advance(id)
+        .map_or(ascender - f32::from(ttf.descender()), f32::from);
+    let x = f32::from(ttf.glyph_hor_advance(id).unwrap_or(0)) / 2.0;
+    let y = ttf
+        .glyph_y_origin(id)
+        .map(f32::from)
+        .or_else(|| {
+            let bearing = ttf.glyph_ver_side_bearing(id)?;
+            let bbox = ttf.glyph_bounding_box(id)?;
+            Some(f32::from(bearing) + f32::from(bbox.y_max))
+        })
+        .unwrap_or(ascender);
+    (advance, x, y)
+}
+
 /// Extra methods for [`[T]`](slice).
 pub trait SliceExt<T> {
     /// Split a slice into consecutive runs with the same key and yield for
@@ -478,58 +1187,122 @@ where
 pub struct Font {
     pub glyph_set: BTreeMap<u16, String>,
     pub reference: Ref,
-    pub face_data: Vec<u8>,
-    pub units_per_em: u16,
-    pub face_index: u32,
+    /// The used glyphs that have color, sorted by their id. Instead of the CID
+    /// font, they are written into Type 3 fonts with up to 256 glyphs each.
+    pub color_glyphs: Vec<u16>,
+    /// The references of the Type 3 fonts for the color glyphs.
+    pub type3_refs: Vec<Ref>,
+    /// The reference of the Type 0 font with the `Identity-V` encoding, if glyphs
+    /// of the font are used in vertical text. It shares the CID font with the
+    /// horizontal one.
+    pub vertical_reference: Option<Ref>,
+    /// The face of the font, which may be shared with other conversions.
+    pub face: Arc<CachedFace>,
+}
+
+impl Font {
+    /// Find the used glyphs that have color and allocate the Type 3 fonts they
+    /// will be written into. Must be called once all glyphs have been collected.
+    pub fn find_color_glyphs(&mut self, alloc: &mut RefAllocator) {
+        if !self.face.has_color {
+            return;
+        }
+
+        let ttf = self.face.ttf();
+        self.color_glyphs = self
+            .glyph_set
+            .keys()
+            .copied()
+            .filter(|&g| is_color_glyph(&ttf, GlyphId(g)))
+            .collect();
+        self.type3_refs =
+            self.color_glyphs.chunks(256).map(|_| alloc.alloc_ref()).collect();
+    }
+
+    /// Get the font a glyph is written into and the character code that shows it,
+    /// if it is a color glyph.
+    pub fn type3_glyph(&self, gid: u16) -> Option<(Ref, u8)> {
+        let index = self.color_glyphs.binary_search(&gid).ok()?;
+        Some((self.type3_refs[index / 256], (index % 256) as u8))
+    }
 }
 
-pub fn fill_fonts(group: &Group, ctx: &mut Context, fontdb: &fontdb::Database) {
+pub fn fill_fonts(
+    group: &Group,
+    ctx: &mut Context,
+    fontdb: &fontdb::Database,
+) -> Result<()> {
     for child in group.children() {
         match child {
             Node::Text(t) => {
-                let allocator = &mut ctx.ref_allocator;
                 for span in t.layouted() {
                     for g in &span.positioned_glyphs {
-                        let font = ctx.fonts.entry(g.font).or_insert_with(|| {
-                            fontdb
-                                .with_face_data(g.font, |data, face_index| {
-                                    // TODO: Currently, we are parsing each font twice, once here
-                                    // and once again when writing the fonts. We should probably
-                                    // improve on that...
-                                    if let Ok(ttf) =
-                                        ttf_parser::Face::parse(data, face_index)
-                                    {
-                                        let reference = allocator.alloc_ref();
-                                        let glyph_set = BTreeMap::new();
-                                        return Some(Font {
-                                            reference,
-                                            face_data: Vec::from(data),
-                                            units_per_em: ttf.units_per_em(),
-                                            glyph_set,
-                                            face_index,
-                                        });
-                                    }
-
-                                    None
-                                })
-                                .flatten()
-                        });
-
-                        if let Some(ref mut font) = font {
-                            font.glyph_set.insert(g.glyph_id.0, g.text.clone());
+                        if !ctx.fonts.contains_key(&g.font) {
+                            let font = load_font(g.font, ctx, fontdb)?;
+                            ctx.fonts.insert(g.font, font);
+                        }
+
+                        if let Some(Some(font)) = ctx.fonts.get_mut(&g.font) {
+                            font.glyph_set
+                                .entry(g.glyph_id.0)
+                                .or_insert_with(|| g.text.clone());
+                            if font.vertical_reference.is_none() && is_vertical(t, g) {
+                                font.vertical_reference =
+                                    Some(ctx.ref_allocator.alloc_ref());
+                            }
                         }
                     }
                 }
             }
-            Node::Group(group) => fill_fonts(group, ctx, fontdb),
+            Node::Group(group) => fill_fonts(group, ctx, fontdb)?,
             Node::Image(image) => {
                 if let ImageKind::SVG(svg) = image.kind() {
-                    fill_fonts(svg.root(), ctx, fontdb);
+                    fill_fonts(svg.root(), ctx, fontdb)?;
                 }
             }
             _ => {}
         }
 
-        child.subroots(|subroot| fill_fonts(subroot, ctx, fontdb));
+        let mut result = Ok(());
+        child.subroots(|subroot| {
+            if result.is_ok() {
+                result = fill_fonts(subroot, ctx, fontdb);
+            }
+        });
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Load a font from the database. In lenient mode, fonts that cannot be parsed
+/// are skipped, meaning that text using them will not be rendered.
+fn load_font(
+    id: ID,
+    ctx: &mut Context,
+    fontdb: &fontdb::Database,
+) -> Result<Option<Font>> {
+    let font = ctx.font_cache.load(id, fontdb).map(|face| Font {
+        reference: ctx.ref_allocator.alloc_ref(),
+        glyph_set: BTreeMap::new(),
+        color_glyphs: vec![],
+        type3_refs: vec![],
+        vertical_reference: None,
+        face,
+    });
+
+    if font.is_none() {
+        let name = fontdb
+            .face(id)
+            .map(|face| face.post_script_name.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        let err = ConversionError::InvalidFont(name);
+        if !ctx.options.lenient {
+            return Err(err);
+        }
+
+        log::warn!("{err}. Skipping text using it.");
     }
+
+    Ok(font)
 }
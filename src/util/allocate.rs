@@ -17,6 +17,11 @@ impl RefAllocator {
     pub fn alloc_ref(&mut self) -> Ref {
         self.ref_alloc.bump()
     }
+
+    /// The reference that will be allocated next.
+    pub fn next_ref(&self) -> Ref {
+        self.ref_alloc
+    }
 }
 
 /// Struct that keeps track name allocations in a XObject/Page.
@@ -0,0 +1,268 @@
+//! Conversion of fonts with CFF2 outlines into bare CFF font programs, since PDFs
+//! before version 2.0 can only embed the latter.
+
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+/// The string ids of "Adobe" and "Identity" in the string index that we write,
+/// which come after the 391 standard strings.
+const ADOBE_SID: i32 = 391;
+const IDENTITY_SID: i32 = 392;
+
+/// Create a CID-keyed CFF font program with the outlines of some glyphs of a face.
+///
+/// The outlines are taken from the instance selected by the variation coordinates
+/// of the face and are scaled to 1000 units per em. All other glyphs up to the
+/// largest given one are left empty, so that the CIDs are the same as the glyph
+/// ids. Hints are not retained.
+pub fn convert(ttf: &Face, name: &str, glyphs: &[u16]) -> Vec<u8> {
+    let scale = 1000.0 / f64::from(ttf.units_per_em());
+    let count = glyphs.iter().max().map_or(1, |&g| usize::from(g) + 1);
+
+    let mut char_strings = vec![vec![ENDCHAR]; count];
+    for &g in glyphs {
+        let mut writer = CharStringWriter { data: vec![], scale, x: 0, y: 0 };
+        if ttf.outline_glyph(GlyphId(g), &mut writer).is_some() {
+            writer.data.push(ENDCHAR);
+            char_strings[usize::from(g)] = writer.data;
+        }
+    }
+
+    let bbox = ttf.global_bounding_box();
+    let bbox = [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max]
+        .map(|v| (f64::from(v) * scale).round() as i32);
+
+    let mut private = vec![];
+    // defaultWidthX, the widths are taken from the PDF instead.
+    push_dict_int(&mut private, 0);
+    private.push(20);
+
+    let mut charset = vec![];
+    if count > 1 {
+        // Format 2 with a single range that maps all glyphs to the CID with the
+        // same number.
+        charset.push(2);
+        charset.extend(1u16.to_be_bytes());
+        charset.extend((count as u16 - 2).to_be_bytes());
+    } else {
+        charset.push(0);
+    }
+
+    // Format 3 with a single range that assigns all glyphs to the first font dict.
+    let mut fd_select = vec![3];
+    fd_select.extend(1u16.to_be_bytes());
+    fd_select.extend(0u16.to_be_bytes());
+    fd_select.push(0);
+    fd_select.extend((count as u16).to_be_bytes());
+
+    let mut char_strings_index = vec![];
+    write_index(&mut char_strings_index, &char_strings);
+
+    let mut strings = vec![];
+    write_index(&mut strings, &[b"Adobe".as_slice(), b"Identity".as_slice()]);
+
+    let mut names = vec![];
+    write_index(&mut names, &[name.as_bytes()]);
+
+    // The offsets in the top dict are written with a fixed size, so its length
+    // doesn't depend on them.
+    let top_dict = |offsets: [i32; 4]| {
+        let [charset, fd_select, char_strings, fd_array] = offsets;
+        let mut dict = vec![];
+        for operand in [ADOBE_SID, IDENTITY_SID, 0] {
+            push_dict_int(&mut dict, operand);
+        }
+        dict.extend([12, 30]);
+        push_dict_int(&mut dict, count as i32);
+        dict.extend([12, 34]);
+        for v in bbox {
+            push_dict_int(&mut dict, v);
+        }
+        dict.push(5);
+        push_dict_offset(&mut dict, charset);
+        dict.push(15);
+        push_dict_offset(&mut dict, fd_select);
+        dict.extend([12, 37]);
+        push_dict_offset(&mut dict, char_strings);
+        dict.push(17);
+        push_dict_offset(&mut dict, fd_array);
+        dict.extend([12, 36]);
+        let mut index = vec![];
+        write_index(&mut index, &[dict]);
+        index
+    };
+
+    let font_dict = |private_offset: i32| {
+        let mut dict = vec![];
+        push_dict_offset(&mut dict, private.len() as i32);
+        push_dict_offset(&mut dict, private_offset);
+        dict.push(18);
+        let mut index = vec![];
+        write_index(&mut index, &[dict]);
+        index
+    };
+
+    // Header, name index, top dict index, string index and the empty global
+    // subroutine index.
+    let header_len = 4 + names.len() + top_dict([0; 4]).len() + strings.len() + 2;
+    let charset_offset = header_len;
+    let fd_select_offset = charset_offset + charset.len();
+    let char_strings_offset = fd_select_offset + fd_select.len();
+    let fd_array_offset = char_strings_offset + char_strings_index.len();
+    let private_offset = fd_array_offset + font_dict(0).len();
+
+    let mut data = vec![1, 0, 4, 4];
+    data.extend(names);
+    data.extend(top_dict(
+        [charset_offset, fd_select_offset, char_strings_offset, fd_array_offset]
+            .map(|offset| offset as i32),
+    ));
+    data.extend(strings);
+    data.extend([0, 0]);
+    data.extend(charset);
+    data.extend(fd_select);
+    data.extend(char_strings_index);
+    data.extend(font_dict(private_offset as i32));
+    data.extend(private);
+    data
+}
+
+const RMOVETO: u8 = 21;
+const RLINETO: u8 = 5;
+const RRCURVETO: u8 = 8;
+const ENDCHAR: u8 = 14;
+
+/// Writes an outline as a Type 2 charstring. Coordinates are tracked in 16.16
+/// fixed point numbers, so that the relative operators don't accumulate rounding
+/// errors.
+struct CharStringWriter {
+    data: Vec<u8>,
+    scale: f64,
+    x: i32,
+    y: i32,
+}
+
+impl CharStringWriter {
+    fn fixed(&self, v: f32) -> i32 {
+        (f64::from(v) * self.scale * 65536.0).round() as i32
+    }
+
+    /// Push the operands for the points relative to the current point and move
+    /// to the last one.
+    fn push_points(&mut self, points: &[(f32, f32)]) {
+        for &(x, y) in points {
+            let (x, y) = (self.fixed(x), self.fixed(y));
+            push_fixed(&mut self.data, x - self.x);
+            push_fixed(&mut self.data, y - self.y);
+            self.x = x;
+            self.y = y;
+        }
+    }
+
+    /// The current point in font units.
+    fn current(&self) -> (f32, f32) {
+        let unscale = |v: i32| (f64::from(v) / 65536.0 / self.scale) as f32;
+        (unscale(self.x), unscale(self.y))
+    }
+}
+
+impl OutlineBuilder for CharStringWriter {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.push_points(&[(x, y)]);
+        self.data.push(RMOVETO);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push_points(&[(x, y)]);
+        self.data.push(RLINETO);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x0, y0) = self.current();
+        self.push_points(&[
+            (x0 + 2.0 / 3.0 * (x1 - x0), y0 + 2.0 / 3.0 * (y1 - y0)),
+            (x + 2.0 / 3.0 * (x1 - x), y + 2.0 / 3.0 * (y1 - y)),
+            (x, y),
+        ]);
+        self.data.push(RRCURVETO);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.push_points(&[(x1, y1), (x2, y2), (x, y)]);
+        self.data.push(RRCURVETO);
+    }
+
+    // Subpaths are closed implicitly.
+    fn close(&mut self) {}
+}
+
+/// Write an INDEX structure.
+fn write_index(out: &mut Vec<u8>, items: &[impl AsRef<[u8]>]) {
+    out.extend((items.len() as u16).to_be_bytes());
+    if items.is_empty() {
+        return;
+    }
+
+    let last_offset = 1 + items.iter().map(|item| item.as_ref().len()).sum::<usize>();
+    let offset_size = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFFFFFF => 3,
+        _ => 4,
+    };
+
+    out.push(offset_size as u8);
+    let mut offset = 1;
+    for item in std::iter::once([].as_slice()).chain(items.iter().map(AsRef::as_ref)) {
+        offset += item.len();
+        out.extend(&(offset as u32).to_be_bytes()[4 - offset_size..]);
+    }
+
+    for item in items {
+        out.extend(item.as_ref());
+    }
+}
+
+/// Push an integer operand in its shortest encoding, which is the same for
+/// dicts and charstrings as long as it fits into 16 bits.
+fn push_short_int(out: &mut Vec<u8>, v: i16) {
+    match v {
+        -107..=107 => out.push((v + 139) as u8),
+        108..=1131 => {
+            let v = v - 108;
+            out.extend([(v >> 8) as u8 + 247, v as u8]);
+        }
+        -1131..=-108 => {
+            let v = -v - 108;
+            out.extend([(v >> 8) as u8 + 251, v as u8]);
+        }
+        _ => {
+            out.push(28);
+            out.extend(v.to_be_bytes());
+        }
+    }
+}
+
+/// Push an integer operand into a dict.
+fn push_dict_int(out: &mut Vec<u8>, v: i32) {
+    match i16::try_from(v) {
+        Ok(v) => push_short_int(out, v),
+        Err(_) => push_dict_offset(out, v),
+    }
+}
+
+/// Push an integer operand into a dict with a fixed size of five bytes.
+fn push_dict_offset(out: &mut Vec<u8>, v: i32) {
+    out.push(29);
+    out.extend(v.to_be_bytes());
+}
+
+/// Push a 16.16 fixed point operand into a charstring.
+fn push_fixed(out: &mut Vec<u8>, v: i32) {
+    match i16::try_from(v >> 16) {
+        Ok(int) if v & 0xFFFF == 0 => push_short_int(out, int),
+        _ => {
+            out.push(255);
+            out.extend(v.to_be_bytes());
+        }
+    }
+}
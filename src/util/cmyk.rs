@@ -0,0 +1,80 @@
+use moxcms::{
+    CmsError, ColorProfile, DataColorSpace, Layout, ProfileText, TransformF32BitExecutor,
+    TransformOptions,
+};
+use pdf_writer::{Chunk, Filter, Ref};
+
+use crate::util::helper::deflate;
+use crate::{ConversionError, Result};
+
+/// A CMYK output profile, into which solid colors and gradients are converted.
+pub struct CmykProfile {
+    /// The reference to the ICC profile stream.
+    pub reference: Ref,
+    /// The description of the profile, which identifies the output condition.
+    pub description: String,
+    data: Vec<u8>,
+    transform: Box<TransformF32BitExecutor>,
+}
+
+impl CmykProfile {
+    /// Parse a CMYK ICC profile and prepare the conversion from sRGB into it.
+    pub fn new(data: &[u8], reference: Ref) -> Result<Self> {
+        let invalid =
+            |err: CmsError| ConversionError::InvalidColorProfile(err.to_string());
+
+        let profile = ColorProfile::new_from_slice(data).map_err(invalid)?;
+        if profile.color_space != DataColorSpace::Cmyk {
+            return Err(ConversionError::InvalidColorProfile(
+                "not a CMYK profile".to_string(),
+            ));
+        }
+
+        let transform = ColorProfile::new_srgb()
+            .create_transform_f32(
+                Layout::Rgb,
+                &profile,
+                Layout::Rgba,
+                TransformOptions::default(),
+            )
+            .map_err(invalid)?;
+
+        let description = profile
+            .description
+            .as_ref()
+            .and_then(|text| match text {
+                ProfileText::PlainString(string) => Some(string.clone()),
+                ProfileText::Localizable(strings) => {
+                    strings.first().map(|string| string.value.clone())
+                }
+                ProfileText::Description(string) => Some(string.ascii_string.clone()),
+            })
+            .map(|string| string.trim_end_matches('\0').to_string())
+            .filter(|string| !string.is_empty())
+            .unwrap_or_else(|| "Custom CMYK profile".to_string());
+
+        Ok(Self {
+            reference,
+            description,
+            data: data.to_vec(),
+            transform,
+        })
+    }
+
+    /// Convert an sRGB color into CMYK.
+    pub fn convert(&self, color: [f32; 3]) -> [f32; 4] {
+        let mut cmyk = [0.0; 4];
+        // Both buffers hold exactly one color, so this can't fail.
+        let _ = self.transform.transform(&color, &mut cmyk);
+        cmyk.map(|c| c.clamp(0.0, 1.0))
+    }
+
+    /// Write the ICC profile stream.
+    pub fn write(&self, chunk: &mut Chunk) {
+        chunk
+            .icc_profile(self.reference, &deflate(&self.data))
+            .n(4)
+            .range([0.0, 1.0].repeat(4))
+            .filter(Filter::FlateDecode);
+    }
+}
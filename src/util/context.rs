@@ -1,9 +1,14 @@
+use std::collections::BTreeMap;
+
 use pdf_writer::{Chunk, Content, Filter, Ref};
+use usvg::{NonZeroRect, Transform};
 
 #[cfg(feature = "text")]
 use {
     crate::render::text,
     crate::render::text::{write_font, Font},
+    crate::util::font_cache::FontCache,
+    crate::ConversionError,
     std::collections::HashMap,
     usvg::fontdb,
     usvg::fontdb::ID,
@@ -12,7 +17,11 @@ use {
 
 use super::helper::deflate;
 use crate::util::allocate::RefAllocator;
-use crate::{ConversionOptions, GRAY_ICC_DEFLATED, SRGB_ICC_DEFLATED};
+use crate::util::cmyk::CmykProfile;
+use crate::util::tags::{self, Tags};
+use crate::{ConversionOptions, Result, GRAY_ICC_DEFLATED, SRGB_ICC_DEFLATED};
+#[cfg(feature = "image")]
+use {super::helper::hash128, std::collections::HashSet, usvg::Size};
 
 /// Holds all of the necessary information for the conversion process.
 pub struct Context {
@@ -21,46 +30,171 @@ pub struct Context {
     /// The refs of the fonts
     #[cfg(feature = "text")]
     pub fonts: HashMap<ID, Option<Font>>,
+    /// The cache the faces of the fonts are loaded from, which is a new one unless
+    /// one was passed in the options.
+    #[cfg(feature = "text")]
+    pub font_cache: FontCache,
     srgb_ref: Option<Ref>,
     sgray_ref: Option<Ref>,
+    /// The ICC profiles embedded in images, deduplicated by their hash.
+    icc_profiles: BTreeMap<u128, IccProfile>,
+    /// The image XObjects that were already written, by the hash of their content,
+    /// together with the size of the image.
+    #[cfg(feature = "image")]
+    pub images: BTreeMap<u128, (Ref, Size)>,
+    /// The addresses of the SVG images that have no possible duplicate, see
+    /// [`image::unique_svg_images`](crate::render::image::unique_svg_images).
+    #[cfg(feature = "image")]
+    pub unique_svg_images: HashSet<usize>,
+    /// The CMYK output profile, if one was given.
+    pub cmyk: Option<CmykProfile>,
+    /// The part of the current tree that can end up being visible, in the coordinate
+    /// system of its root. `None` if it is not known, e.g. inside of a pattern.
+    pub visible_area: Option<NonZeroRect>,
+    /// The transform from the coordinate system of the root of the current tree into
+    /// points on the page. `None` if it is not known, e.g. inside of a pattern.
+    pub root_transform: Option<Transform>,
+    /// The structure elements of a tagged document.
+    pub tags: Tags,
+    /// Whether one of the nodes that are currently rendered is skipped as a whole if
+    /// it fails in lenient mode.
+    pub in_skippable_node: bool,
     pub ref_allocator: RefAllocator,
 }
 
+/// The state of a context at some point, which it can be reset to.
+#[derive(Copy, Clone)]
+pub struct Checkpoint {
+    ref_allocator: RefAllocator,
+    tags: tags::Checkpoint,
+}
+
+/// An ICC profile that was embedded in an image.
+struct IccProfile {
+    reference: Ref,
+    data: Vec<u8>,
+    components: i32,
+}
+
 impl Context {
-    /// Create a new context.
+    /// Create a new context. All trees that will be written using this
+    /// context need to be provided, so that their fonts can be collected.
     #[cfg(feature = "text")]
     pub fn new(
-        tree: &Tree,
+        trees: &[&Tree],
         options: ConversionOptions,
         fontdb: &fontdb::Database,
-    ) -> Self {
+    ) -> Result<Self> {
+        let mut ref_allocator = RefAllocator::new();
+        let cmyk = load_cmyk_profile(&options, &mut ref_allocator)?;
+        let font_cache = options.font_cache.clone().unwrap_or_default();
         let mut ctx = Self {
-            ref_allocator: RefAllocator::new(),
+            ref_allocator,
             options,
             fonts: HashMap::new(),
+            font_cache,
             srgb_ref: None,
             sgray_ref: None,
+            icc_profiles: BTreeMap::new(),
+            #[cfg(feature = "image")]
+            images: BTreeMap::new(),
+            #[cfg(feature = "image")]
+            unique_svg_images: HashSet::new(),
+            cmyk,
+            visible_area: None,
+            root_transform: None,
+            tags: Tags::default(),
+            in_skippable_node: false,
         };
 
-        if options.embed_text {
-            text::fill_fonts(tree.root(), &mut ctx, fontdb);
+        if ctx.options.embed_text {
+            for tree in trees {
+                text::fill_fonts(tree.root(), &mut ctx, fontdb)?;
+            }
+
+            for font in ctx.fonts.values_mut().flatten() {
+                text::resolve_texts(&font.face, &mut font.glyph_set);
+                font.find_color_glyphs(&mut ctx.ref_allocator);
+            }
         }
 
-        ctx
+        Ok(ctx)
     }
 
     // TODO: Make context less ugly with different features.
     /// Create a new context.
     #[cfg(not(feature = "text"))]
-    pub fn new(options: ConversionOptions) -> Self {
-        Self {
-            ref_allocator: RefAllocator::new(),
+    pub fn new(options: ConversionOptions) -> Result<Self> {
+        let mut ref_allocator = RefAllocator::new();
+        let cmyk = load_cmyk_profile(&options, &mut ref_allocator)?;
+        Ok(Self {
+            ref_allocator,
             options,
             srgb_ref: None,
             sgray_ref: None,
+            icc_profiles: BTreeMap::new(),
+            #[cfg(feature = "image")]
+            images: BTreeMap::new(),
+            #[cfg(feature = "image")]
+            unique_svg_images: HashSet::new(),
+            cmyk,
+            visible_area: None,
+            root_transform: None,
+            tags: Tags::default(),
+            in_skippable_node: false,
+        })
+    }
+
+    /// Run `f` with the visible area set to `area` and restore the previous one
+    /// afterwards.
+    pub fn with_visible_area<T>(
+        &mut self,
+        area: Option<NonZeroRect>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let previous = std::mem::replace(&mut self.visible_area, area);
+        let result = f(self);
+        self.visible_area = previous;
+        result
+    }
+
+    /// Run `f` for the root of a tree or a subtree, e.g. the content of a pattern,
+    /// with the given visible area and root transform, and restore the previous ones
+    /// afterwards.
+    pub fn with_root<T>(
+        &mut self,
+        visible_area: Option<NonZeroRect>,
+        root_transform: Option<Transform>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let previous = std::mem::replace(&mut self.root_transform, root_transform);
+        let result = self.with_visible_area(visible_area, f);
+        self.root_transform = previous;
+        result
+    }
+
+    /// Remember the current state of the context, see [`Context::reset`].
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            ref_allocator: self.ref_allocator,
+            tags: self.tags.checkpoint(),
         }
     }
 
+    /// Forget all references, cached objects and structure elements that were
+    /// added since the checkpoint, so that the objects written since then can be
+    /// discarded.
+    pub fn reset(&mut self, checkpoint: Checkpoint) {
+        let first_new = checkpoint.ref_allocator.next_ref();
+        self.ref_allocator = checkpoint.ref_allocator;
+        self.srgb_ref = self.srgb_ref.filter(|r| *r < first_new);
+        self.sgray_ref = self.sgray_ref.filter(|r| *r < first_new);
+        self.icc_profiles.retain(|_, profile| profile.reference < first_new);
+        #[cfg(feature = "image")]
+        self.images.retain(|_, (reference, _)| *reference < first_new);
+        self.tags.reset(checkpoint.tags);
+    }
+
     /// Allocate a new reference.
     pub fn alloc_ref(&mut self) -> Ref {
         self.ref_allocator.alloc_ref()
@@ -80,6 +214,38 @@ impl Context {
         *sgray_ref.get_or_insert_with(|| alloc.alloc_ref())
     }
 
+    /// Get the reference to the color space that solid colors and gradients are
+    /// written in, i.e. the CMYK output profile if one was given and sRGB otherwise.
+    pub fn color_space_ref(&mut self) -> Ref {
+        match &self.cmyk {
+            Some(cmyk) => cmyk.reference,
+            None => self.srgb_ref(),
+        }
+    }
+
+    /// Convert an sRGB color into the color space of [`Context::color_space_ref`].
+    pub fn convert_color(&self, color: [f32; 3]) -> Vec<f32> {
+        match &self.cmyk {
+            Some(cmyk) => cmyk.convert(color).to_vec(),
+            None => color.to_vec(),
+        }
+    }
+
+    /// Get the reference to an ICC profile with the given number of color
+    /// components. Identical profiles will only be written once.
+    #[cfg(feature = "image")]
+    pub fn icc_profile_ref(&mut self, profile: &[u8], components: i32) -> Ref {
+        let alloc = &mut self.ref_allocator;
+        self.icc_profiles
+            .entry(hash128(&profile))
+            .or_insert_with(|| IccProfile {
+                reference: alloc.alloc_ref(),
+                data: deflate(profile),
+                components,
+            })
+            .reference
+    }
+
     #[cfg(feature = "text")]
     pub fn font_ref(&self, id: ID) -> Option<&Font> {
         self.fonts.get(&id).and_then(|f| f.as_ref())
@@ -88,13 +254,14 @@ impl Context {
     pub fn write_global_objects(&mut self, pdf: &mut Chunk) {
         #[cfg(feature = "text")]
         {
-            let allocator = &mut self.ref_allocator;
-
-            for font in self.fonts.values_mut() {
-                if let Some(font) = font.as_mut() {
-                    write_font(pdf, allocator, font);
-                }
+            // Color glyphs are drawn with the whole context, so the fonts can't be
+            // borrowed from it while they are written.
+            let fonts = std::mem::take(&mut self.fonts);
+            for font in fonts.values().flatten() {
+                write_font(pdf, self, font);
             }
+
+            self.fonts = fonts;
         }
 
         if let Some(srgb_ref) = self.srgb_ref {
@@ -110,6 +277,38 @@ impl Context {
                 .range([0.0, 1.0])
                 .filter(Filter::FlateDecode);
         }
+
+        if let Some(cmyk) = &self.cmyk {
+            cmyk.write(pdf);
+        }
+
+        for profile in self.icc_profiles.values() {
+            pdf.icc_profile(profile.reference, &profile.data)
+                .n(profile.components)
+                .filter(Filter::FlateDecode);
+        }
+    }
+
+    /// Check whether the collected resources can be written in a way that conforms
+    /// to PDF/A.
+    pub fn check_pdfa(&self) -> Result<()> {
+        #[cfg(feature = "text")]
+        for font in self.fonts.values().flatten() {
+            let name = &font.face.postscript_name;
+            if font.face.is_restricted {
+                return Err(ConversionError::PdfA(format!(
+                    "the font {name} does not allow embedding"
+                )));
+            }
+
+            if font.glyph_set.contains_key(&0) {
+                return Err(ConversionError::PdfA(format!(
+                    "the text references the .notdef glyph of the font {name}"
+                )));
+            }
+        }
+
+        Ok(())
     }
 
     /// Just a helper method so that we don't have to manually compress the content if this was
@@ -122,3 +321,15 @@ impl Context {
         }
     }
 }
+
+/// Load the CMYK output profile from the options, if one was given.
+fn load_cmyk_profile(
+    options: &ConversionOptions,
+    ref_allocator: &mut RefAllocator,
+) -> Result<Option<CmykProfile>> {
+    options
+        .cmyk_profile
+        .as_deref()
+        .map(|data| CmykProfile::new(data, ref_allocator.alloc_ref()))
+        .transpose()
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use pdf_writer::types::FontFlags;
+use pdf_writer::Rect;
+use ttf_parser::{name_id, Face, GlyphId, Permissions, Tag};
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+use usvg::fontdb::{self, Source, ID};
+
+use crate::render::text::find_name;
+
+const CFF: Tag = Tag::from_bytes(b"CFF ");
+const CFF2: Tag = Tag::from_bytes(b"CFF2");
+
+/// A cache for the fonts of embedded text that can be shared between conversions
+/// and threads.
+///
+/// Without a cache, each conversion loads the fonts it uses from the font database
+/// and parses them. With one, this only happens the first time a font is used,
+/// so that only the subsetting of the fonts remains for each conversion. Cloning
+/// the cache is cheap and yields a handle to the same cache.
+///
+/// Fonts are identified by the file or the shared data they were loaded from in
+/// the [font database](fontdb::Database), so the cache should be used together
+/// with the same database. Fonts loaded from memory are kept alive by the cache
+/// until it is [cleared](FontCache::clear).
+///
+/// ## Example
+/// ```
+/// use svg2pdf::{ConversionOptions, FontCache};
+///
+/// let cache = FontCache::new();
+/// let options = ConversionOptions {
+///     font_cache: Some(cache.clone()),
+///     ..ConversionOptions::default()
+/// };
+/// ```
+#[derive(Clone, Default)]
+pub struct FontCache {
+    faces: Arc<Mutex<HashMap<FaceKey, Option<Arc<CachedFace>>>>>,
+}
+
+impl FontCache {
+    /// Create a new, empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all fonts from the cache.
+    pub fn clear(&self) {
+        self.faces.lock().unwrap().clear();
+    }
+
+    /// Get a face from the cache or load it from the database. Returns `None` if
+    /// it cannot be parsed.
+    pub(crate) fn load(
+        &self,
+        id: ID,
+        fontdb: &fontdb::Database,
+    ) -> Option<Arc<CachedFace>> {
+        let (source, index) = fontdb.face_source(id)?;
+        let key = match &source {
+            Source::Binary(data) => {
+                FaceKey::Memory(Arc::as_ptr(data) as *const u8 as usize, index)
+            }
+            Source::File(path) | Source::SharedFile(path, _) => {
+                FaceKey::File(path.clone(), index)
+            }
+        };
+
+        if let Some(face) = self.faces.lock().unwrap().get(&key) {
+            return face.clone();
+        }
+
+        // The lock isn't held while loading the face, so that other threads can
+        // use the cache in the meantime.
+        let data = match source {
+            Source::Binary(data) | Source::SharedFile(_, data) => data,
+            Source::File(_) => fontdb.with_face_data(id, |data, _| {
+                Arc::new(data.to_vec()) as Arc<dyn AsRef<[u8]> + Send + Sync>
+            })?,
+        };
+
+        let face = CachedFace::new(data, index).map(Arc::new);
+        self.faces.lock().unwrap().entry(key).or_insert(face).clone()
+    }
+}
+
+/// Identifies where a face was loaded from.
+#[derive(Clone, Eq, PartialEq, Hash)]
+enum FaceKey {
+    /// The path of the file and the index of the face in it.
+    File(PathBuf, u32),
+    /// The address of the data and the index of the face in it. The address stays
+    /// unique since the cached face keeps the data alive.
+    Memory(usize, u32),
+}
+
+/// A parsed face together with everything about it that doesn't depend on the
+/// text that uses it.
+pub struct CachedFace {
+    /// The data of the whole font file.
+    data: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    /// The index of the face in the font file.
+    pub index: u32,
+    pub units_per_em: u16,
+    pub postscript_name: String,
+    /// Whether the face has CFF outlines.
+    pub is_cff: bool,
+    /// Whether the face has CFF2 outlines (and no CFF outlines).
+    pub is_cff2: bool,
+    /// Whether the face has tables with color glyphs.
+    pub has_color: bool,
+    /// Whether the face must not be embedded.
+    pub is_restricted: bool,
+    pub flags: FontFlags,
+    /// The bounding box in units of 1/1000 em.
+    pub bbox: Rect,
+    pub italic_angle: f32,
+    pub ascender: f32,
+    pub descender: f32,
+    pub cap_height: f32,
+    pub stem_v: f32,
+    /// For each glyph that the font's cmap maps a codepoint that isn't private-use
+    /// to, the last such codepoint.
+    pub codepoints: HashMap<u16, char>,
+}
+
+impl CachedFace {
+    fn new(data: Arc<dyn AsRef<[u8]> + Send + Sync>, index: u32) -> Option<Self> {
+        let ttf = Face::parse((*data).as_ref(), index).ok()?;
+        let units_per_em = ttf.units_per_em();
+        let raw = ttf.raw_face();
+        let is_cff = raw.table(CFF).is_some();
+        let is_cff2 = !is_cff && raw.table(CFF2).is_some();
+        let tables = ttf.tables();
+        let has_color = tables.colr.is_some()
+            || tables.svg.is_some()
+            || tables.sbix.is_some()
+            || tables.cbdt.is_some();
+
+        let postscript_name = find_name(&ttf, name_id::POST_SCRIPT_NAME)
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let mut flags = FontFlags::empty();
+        flags.set(FontFlags::SERIF, postscript_name.contains("Serif"));
+        flags.set(FontFlags::FIXED_PITCH, ttf.is_monospaced());
+        flags.set(FontFlags::ITALIC, ttf.is_italic());
+        flags.insert(FontFlags::SYMBOLIC);
+        flags.insert(FontFlags::SMALL_CAP);
+
+        let global_bbox = ttf.global_bounding_box();
+        let bbox = Rect::new(
+            (global_bbox.x_min as f32 / units_per_em as f32) * 1000.0,
+            (global_bbox.y_min as f32 / units_per_em as f32) * 1000.0,
+            (global_bbox.x_max as f32 / units_per_em as f32) * 1000.0,
+            (global_bbox.y_max as f32 / units_per_em as f32) * 1000.0,
+        );
+
+        let italic_angle = ttf.italic_angle().unwrap_or(0.0);
+        let ascender = ttf.typographic_ascender().unwrap_or(ttf.ascender());
+        let descender = ttf.typographic_descender().unwrap_or(ttf.descender());
+        let cap_height = ttf.capital_height().filter(|&h| h > 0).unwrap_or(ascender);
+        let stem_v = 10.0 + 0.244 * (f32::from(ttf.weight().to_number()) - 50.0);
+
+        let mut codepoints = HashMap::new();
+        for subtable in ttf.tables().cmap.into_iter().flat_map(|table| table.subtables) {
+            if !subtable.is_unicode() {
+                continue;
+            }
+
+            subtable.codepoints(|n| {
+                let Some(c) = std::char::from_u32(n) else { return };
+                if c.general_category() == GeneralCategory::PrivateUse {
+                    return;
+                }
+
+                let Some(GlyphId(g)) = ttf.glyph_index(c) else { return };
+                codepoints.insert(g, c);
+            });
+        }
+
+        Some(Self {
+            index,
+            units_per_em,
+            postscript_name,
+            is_cff,
+            is_cff2,
+            has_color,
+            is_restricted: ttf.permissions() == Some(Permissions::Restricted),
+            flags,
+            bbox,
+            italic_angle,
+            ascender: ascender as f32,
+            descender: descender as f32,
+            cap_height: cap_height as f32,
+            stem_v,
+            codepoints,
+            data,
+        })
+    }
+
+    /// The data of the whole font file.
+    pub fn data(&self) -> &[u8] {
+        (*self.data).as_ref()
+    }
+
+    /// Parse the face. This is cheap, since the tables are only parsed when they
+    /// are accessed.
+    pub fn ttf(&self) -> Face<'_> {
+        // The face was already parsed successfully when it was loaded.
+        Face::parse(self.data(), self.index).unwrap()
+    }
+}
@@ -1,5 +1,8 @@
+use std::hash::Hash;
+
 use pdf_writer::types::{BlendMode, LineCapStyle, LineJoinStyle, MaskType};
-use pdf_writer::{Content, Name, Rect};
+use pdf_writer::{Content, Name, Primitive, Rect};
+use siphasher::sip128::{Hasher128, SipHasher13};
 #[cfg(feature = "image")]
 use usvg::Size;
 use usvg::{LineCap, LineJoin, NonZeroRect, Transform};
@@ -30,11 +33,11 @@ impl TransformExt for Transform {
 
 /// Extension trait to convert a [String] into a [Name]
 pub trait NameExt {
-    fn to_pdf_name(&self) -> Name;
+    fn to_pdf_name(&self) -> Name<'_>;
 }
 
 impl NameExt for String {
-    fn to_pdf_name(&self) -> Name {
+    fn to_pdf_name(&self) -> Name<'_> {
         Name(self.as_bytes())
     }
 }
@@ -159,12 +162,47 @@ impl GroupExt for usvg::Group {
     }
 }
 
+/// Extension trait to append one content stream to another.
+pub trait ContentExt {
+    /// Append the operations of another content stream.
+    ///
+    /// pdf-writer has no API for writing raw operations, so they are written as the
+    /// only operand of an operation with an empty operator. Finishing that
+    /// operation only adds a space and a newline after them.
+    fn append(&mut self, other: Content);
+}
+
+impl ContentExt for Content {
+    fn append(&mut self, other: Content) {
+        let ops = other.finish();
+        if !ops.is_empty() {
+            self.op("").operand(RawOperations(ops));
+        }
+    }
+}
+
+/// Finished operations of a content stream, which are written as they are.
+struct RawOperations(Vec<u8>);
+
+impl Primitive for RawOperations {
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend(self.0);
+    }
+}
+
 pub fn bbox_to_non_zero_rect(rect: Option<usvg::Rect>) -> NonZeroRect {
     // Convenience method to not panic if bbox is not well-defined
     rect.and_then(|bb| bb.to_non_zero_rect())
         .unwrap_or(NonZeroRect::from_xywh(0.0, 0.0, 1.0, 1.0).unwrap())
 }
 
+/// Calculate the intersection of two rects. Returns `None` if they don't overlap.
+pub fn intersect_rects(a: NonZeroRect, b: NonZeroRect) -> Option<NonZeroRect> {
+    a.to_rect()
+        .intersect(&b.to_rect())
+        .and_then(|rect| rect.to_non_zero_rect())
+}
+
 // Taken from resvg
 /// Calculate the rect of an image after it is scaled using a view box.
 #[cfg(feature = "image")]
@@ -203,6 +241,13 @@ pub fn deflate(data: &[u8]) -> Vec<u8> {
     miniz_oxide::deflate::compress_to_vec_zlib(data, COMPRESSION_LEVEL)
 }
 
+/// Calculate a 128-bit siphash of a value.
+pub fn hash128<T: Hash + ?Sized>(value: &T) -> u128 {
+    let mut state = SipHasher13::new();
+    value.hash(&mut state);
+    state.finish128().as_u128()
+}
+
 pub fn clip_to_rect(rect: NonZeroRect, content: &mut Content) {
     content.rect(rect.x(), rect.y(), rect.width(), rect.height());
     content.close_path();
@@ -0,0 +1,254 @@
+use pdf_writer::{Date, Pdf, Ref, TextStr};
+use xmp_writer::{LangId, Timezone, XmpWriter};
+
+/// The name of the producer that is written into the metadata.
+const PRODUCER: &str = "svg2pdf";
+pub(crate) const SVG_NS: &str = "http://www.w3.org/2000/svg";
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Metadata of the resulting PDF document.
+///
+/// The metadata will be written into the document information dictionary as well
+/// as into an XMP metadata stream. It is only used when writing standalone PDF
+/// files, i.e. it will be ignored by [`to_chunk`](crate::to_chunk).
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Metadata {
+    /// The title of the document.
+    ///
+    /// _Default:_ `None`
+    pub title: Option<String>,
+    /// The name of the person who created the document.
+    ///
+    /// _Default:_ `None`
+    pub author: Option<String>,
+    /// The subject of the document.
+    ///
+    /// _Default:_ `None`
+    pub subject: Option<String>,
+    /// Keywords associated with the document.
+    ///
+    /// _Default:_ empty
+    pub keywords: Vec<String>,
+    /// The name of the application that created the original document, from
+    /// which the PDF was converted.
+    ///
+    /// _Default:_ `None`
+    pub creator: Option<String>,
+    /// The date and time the document was created.
+    ///
+    /// _Default:_ `None`
+    pub creation_date: Option<DateTime>,
+    /// The date and time the document was most recently modified.
+    ///
+    /// _Default:_ `None`
+    pub modification_date: Option<DateTime>,
+    /// The natural language of the document as a BCP 47 language tag, e.g. "en-US".
+    ///
+    /// _Default:_ `None`
+    pub language: Option<String>,
+}
+
+impl Metadata {
+    /// Create metadata from the root of an SVG document.
+    ///
+    /// Since [`usvg` trees](usvg::Tree) don't retain the `<title>` element and the language
+    /// of the document, this method can be used to extract them from the SVG string
+    /// and use them as a default for the title and the language of the document.
+    /// All other fields will be left empty. If the SVG cannot be parsed, the default
+    /// metadata is returned.
+    pub fn from_svg(svg: &str) -> Self {
+        let mut metadata = Self::default();
+        metadata.fill_from_svg(svg);
+        metadata
+    }
+
+    /// Fill in the title and the language of the document from the root of an SVG
+    /// document, if they are not set yet.
+    ///
+    /// Unlike [`Metadata::from_svg`], this keeps all fields that are already set,
+    /// so that the SVG only provides a default for them. If the SVG cannot be
+    /// parsed, the metadata is left unchanged.
+    pub fn fill_from_svg(&mut self, svg: &str) {
+        if self.title.is_some() && self.language.is_some() {
+            return;
+        }
+
+        let Ok(document) = roxmltree::Document::parse(svg) else { return };
+
+        let root = document.root_element();
+
+        self.title = self.title.take().or_else(|| {
+            root.children()
+                .find(|n| n.has_tag_name((SVG_NS, "title")))
+                .and_then(|n| n.text())
+                .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|text| !text.is_empty())
+        });
+
+        self.language = self.language.take().or_else(|| {
+            root.attribute((XML_NS, "lang"))
+                .or_else(|| root.attribute("lang"))
+                .map(|lang| lang.trim().to_string())
+                .filter(|lang| !lang.is_empty())
+        });
+    }
+}
+
+/// A date and time, used for the document metadata.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    /// The year (0-9999).
+    pub year: u16,
+    /// The month (1-12).
+    pub month: u8,
+    /// The day (1-31).
+    pub day: u8,
+    /// The hour (0-23).
+    pub hour: u8,
+    /// The minute (0-59).
+    pub minute: u8,
+    /// The second (0-59).
+    pub second: u8,
+    /// The offset from UTC in hours (-23-23).
+    pub utc_offset_hour: i8,
+    /// The offset from UTC in minutes (0-59). It carries over the sign of
+    /// `utc_offset_hour`.
+    pub utc_offset_minute: u8,
+}
+
+impl DateTime {
+    /// Create a new date and time in UTC.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset_hour: 0,
+            utc_offset_minute: 0,
+        }
+    }
+
+    fn to_pdf_date(self) -> Date {
+        Date::new(self.year)
+            .month(self.month)
+            .day(self.day)
+            .hour(self.hour)
+            .minute(self.minute)
+            .second(self.second)
+            .utc_offset_hour(self.utc_offset_hour)
+            .utc_offset_minute(self.utc_offset_minute)
+    }
+
+    fn to_xmp_date(self) -> xmp_writer::DateTime {
+        let timezone = if self.utc_offset_hour == 0 && self.utc_offset_minute == 0 {
+            Timezone::Utc
+        } else {
+            let minute = self.utc_offset_minute.min(59) as i8;
+            Timezone::Local {
+                hour: self.utc_offset_hour,
+                minute: if self.utc_offset_hour < 0 { -minute } else { minute },
+            }
+        };
+
+        xmp_writer::DateTime::new(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            timezone,
+        )
+    }
+}
+
+/// Write the document information dictionary.
+pub fn write_document_info(pdf: &mut Pdf, reference: Ref, metadata: &Metadata) {
+    let mut info = pdf.document_info(reference);
+    info.producer(TextStr(PRODUCER));
+
+    if let Some(title) = &metadata.title {
+        info.title(TextStr(title));
+    }
+
+    if let Some(author) = &metadata.author {
+        info.author(TextStr(author));
+    }
+
+    if let Some(subject) = &metadata.subject {
+        info.subject(TextStr(subject));
+    }
+
+    if !metadata.keywords.is_empty() {
+        info.keywords(TextStr(&metadata.keywords.join(", ")));
+    }
+
+    if let Some(creator) = &metadata.creator {
+        info.creator(TextStr(creator));
+    }
+
+    if let Some(date) = metadata.creation_date {
+        info.creation_date(date.to_pdf_date());
+    }
+
+    if let Some(date) = metadata.modification_date {
+        info.modified_date(date.to_pdf_date());
+    }
+}
+
+/// Write the XMP metadata stream. If `pdfa` is set, the PDF/A-2b identification
+/// will be written as well.
+pub fn write_xmp_metadata(
+    pdf: &mut Pdf,
+    reference: Ref,
+    metadata: &Metadata,
+    pdfa: bool,
+) {
+    let mut xmp = XmpWriter::new();
+    xmp.format("application/pdf");
+    xmp.producer(PRODUCER);
+
+    if pdfa {
+        xmp.pdfa_part("2");
+        xmp.pdfa_conformance("B");
+    }
+
+    if let Some(title) = &metadata.title {
+        xmp.title([(None, title.as_str())]);
+    }
+
+    if let Some(author) = &metadata.author {
+        xmp.creator([author.as_str()]);
+    }
+
+    if let Some(subject) = &metadata.subject {
+        xmp.description([(None, subject.as_str())]);
+    }
+
+    if !metadata.keywords.is_empty() {
+        xmp.pdf_keywords(&metadata.keywords.join(", "));
+        xmp.subject(metadata.keywords.iter().map(String::as_str));
+    }
+
+    if let Some(creator) = &metadata.creator {
+        xmp.creator_tool(creator);
+    }
+
+    if let Some(date) = metadata.creation_date {
+        xmp.create_date(date.to_xmp_date());
+    }
+
+    if let Some(date) = metadata.modification_date {
+        xmp.modify_date(date.to_xmp_date());
+    }
+
+    if let Some(language) = &metadata.language {
+        xmp.language([LangId(language)]);
+    }
+
+    let xmp_buf = xmp.finish(None);
+    pdf.metadata(reference, xmp_buf.as_bytes());
+}
@@ -1,4 +1,13 @@
 pub mod allocate;
+#[cfg(feature = "text")]
+pub mod cff;
+pub mod cmyk;
 pub mod context;
+#[cfg(feature = "text")]
+pub mod font_cache;
 pub mod helper;
+pub mod metadata;
+#[cfg(feature = "image")]
+pub mod resolver;
 pub mod resources;
+pub mod tags;
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{DynamicImage, ImageFormat};
+#[cfg(feature = "text")]
+use usvg::fontdb;
+use usvg::{ImageHrefResolver, ImageKind, Options};
+
+/// The raster formats that can be loaded in addition to the ones supported by
+/// `usvg`, together with their MIME type.
+const EXTRA_FORMATS: &[(ImageFormat, &str)] = &[
+    #[cfg(feature = "webp")]
+    (ImageFormat::WebP, "image/webp"),
+    #[cfg(feature = "tiff")]
+    (ImageFormat::Tiff, "image/tiff"),
+    #[cfg(feature = "bmp")]
+    (ImageFormat::Bmp, "image/bmp"),
+];
+
+/// Create an image resolver that can load WebP, TIFF and BMP images if the `webp`,
+/// `tiff` and `bmp` features are enabled, in addition to the formats supported by
+/// the [default resolver](ImageHrefResolver::default).
+///
+/// Since [`usvg` trees](usvg::Tree) can only hold JPEG, PNG, GIF and SVG images, the
+/// additional formats are converted into lossless PNG images when the SVG is
+/// parsed. Their ICC profiles are not retained, so they are assumed to be sRGB.
+///
+/// ## Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use svg2pdf::usvg;
+///
+/// let options = usvg::Options {
+///     image_href_resolver: svg2pdf::image_href_resolver(),
+///     ..usvg::Options::default()
+/// };
+/// # Ok(()) }
+/// ```
+pub fn image_href_resolver() -> ImageHrefResolver {
+    let resolve_data = ImageHrefResolver::default_data_resolver();
+    let resolve_string = ImageHrefResolver::default_string_resolver();
+
+    ImageHrefResolver {
+        resolve_data: Box::new(
+            move |mime: &str,
+                  data: Arc<Vec<u8>>,
+                  opts: &Options,
+                  #[cfg(feature = "text")] fontdb: &fontdb::Database| {
+                let format = EXTRA_FORMATS
+                    .iter()
+                    .find(|&&(_, extra_mime)| mime == extra_mime)
+                    .map(|&(format, _)| format)
+                    .or_else(|| extra_format(&data).filter(|_| mime == "text/plain"));
+
+                match format {
+                    Some(format) => convert(&data, format),
+                    None => resolve_data(
+                        mime,
+                        data,
+                        opts,
+                        #[cfg(feature = "text")]
+                        fontdb,
+                    ),
+                }
+            },
+        ),
+        resolve_string: Box::new(
+            move |href: &str,
+                  opts: &Options,
+                  #[cfg(feature = "text")] fontdb: &fontdb::Database| {
+                let path = opts.get_abs_path(Path::new(href));
+                match read_header(&path).as_deref().and_then(extra_format) {
+                    Some(format) => {
+                        let Ok(data) = std::fs::read(&path) else {
+                            log::warn!("Failed to load '{}'. Skipped.", href);
+                            return None;
+                        };
+                        convert(&data, format)
+                    }
+                    None => resolve_string(
+                        href,
+                        opts,
+                        #[cfg(feature = "text")]
+                        fontdb,
+                    ),
+                }
+            },
+        ),
+    }
+}
+
+/// Read the first bytes of a file, which are enough to detect its format.
+fn read_header(path: &Path) -> Option<Vec<u8>> {
+    let mut header = vec![];
+    File::open(path).ok()?.take(16).read_to_end(&mut header).ok()?;
+    Some(header)
+}
+
+/// Detect whether data is in one of the additional formats.
+fn extra_format(data: &[u8]) -> Option<ImageFormat> {
+    let format = image::guess_format(data).ok()?;
+    EXTRA_FORMATS
+        .iter()
+        .any(|&(extra, _)| extra == format)
+        .then_some(format)
+}
+
+/// Convert an image into a PNG image.
+fn convert(data: &[u8], format: ImageFormat) -> Option<ImageKind> {
+    let image = match image::load_from_memory_with_format(data, format) {
+        Ok(image) => image,
+        Err(err) => {
+            log::warn!("Failed to decode {:?} image: {}. Skipped.", format, err);
+            return None;
+        }
+    };
+
+    // PNG has no floating point samples.
+    let image = match image {
+        DynamicImage::ImageRgb32F(_) => image.into_rgb16().into(),
+        DynamicImage::ImageRgba32F(_) => image.into_rgba16().into(),
+        image => image,
+    };
+
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).ok()?;
+    Some(ImageKind::PNG(Arc::new(png)))
+}
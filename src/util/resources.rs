@@ -7,6 +7,7 @@ use pdf_writer::writers::{ColorSpace, Resources};
 use pdf_writer::{Dict, Ref};
 
 use super::helper::NameExt;
+use super::tags::StreamTags;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum PendingResourceType {
@@ -19,7 +20,7 @@ enum PendingResourceType {
 }
 
 impl PendingResourceType {
-    fn get_dict<'a>(&'a self, resources: &'a mut Resources) -> Dict {
+    fn get_dict<'a>(&'a self, resources: &'a mut Resources) -> Dict<'a> {
         match *self {
             PendingResourceType::XObject => resources.x_objects(),
             PendingResourceType::Pattern => resources.patterns(),
@@ -75,6 +76,8 @@ impl PendingResource {
 pub struct ResourceContainer {
     name_allocator: NameAllocator,
     pending_resources: HashMap<Ref, PendingResource>,
+    /// The marked content of the stream, if it is tagged.
+    pub tags: Option<StreamTags>,
 }
 
 impl ResourceContainer {
@@ -117,6 +120,7 @@ impl ResourceContainer {
         Self {
             name_allocator: NameAllocator::default(),
             pending_resources: HashMap::new(),
+            tags: None,
         }
     }
 
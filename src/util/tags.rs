@@ -0,0 +1,292 @@
+//! The structure tree of tagged PDFs.
+
+use std::collections::HashMap;
+
+use pdf_writer::types::StructRole;
+use pdf_writer::writers::{StructElement, StructTreeRoot};
+use pdf_writer::{Chunk, Content, Finish, Name, Ref, TextStr};
+
+use super::allocate::RefAllocator;
+use super::context::Context;
+use super::metadata::SVG_NS;
+use super::resources::ResourceContainer;
+use crate::Result;
+
+/// Collect the alternative texts of the elements of an SVG document with an id.
+///
+/// Since [`usvg` trees](usvg::Tree) don't retain `<title>` and `<desc>` elements
+/// and ARIA labels, this function can be used to extract them from the SVG string
+/// for [`ConversionOptions::alt_texts`](crate::ConversionOptions::alt_texts). The
+/// `aria-label` attribute of an element takes precedence over its title, which in
+/// turn takes precedence over its description. Elements without an id are skipped,
+/// since they cannot be matched to the nodes of the tree. If the SVG cannot be
+/// parsed, no alternative texts are returned.
+pub fn alt_texts_from_svg(svg: &str) -> HashMap<String, String> {
+    let Ok(document) = roxmltree::Document::parse(svg) else {
+        return HashMap::new();
+    };
+
+    let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|n| n.has_tag_name((SVG_NS, name)))
+            .and_then(|n| n.text())
+            .map(normalize)
+            .filter(|text| !text.is_empty())
+    };
+
+    document
+        .descendants()
+        .filter(|node| node.is_element() && *node != document.root_element())
+        .filter_map(|node| {
+            let id = node.attribute("id").filter(|id| !id.is_empty())?;
+            let alt = node
+                .attribute("aria-label")
+                .map(normalize)
+                .filter(|label| !label.is_empty())
+                .or_else(|| child_text(node, "title"))
+                .or_else(|| child_text(node, "desc"))?;
+            Some((id.to_string(), alt))
+        })
+        .collect()
+}
+
+/// The structure elements of a tagged document.
+#[derive(Default)]
+pub struct Tags {
+    elements: Vec<Element>,
+    /// The elements that are direct children of the document element.
+    roots: Vec<usize>,
+    /// For each stream with marked content, the elements that its marked-content ids
+    /// belong to. The index of a stream is its key in the parent tree.
+    parent_tree: Vec<Vec<usize>>,
+    /// The page that is currently being written.
+    pub page: Option<Ref>,
+}
+
+struct Element {
+    role: StructRole,
+    parent: Option<usize>,
+    page: Option<Ref>,
+    alt: Option<String>,
+    actual_text: Option<String>,
+    children: Vec<Child>,
+}
+
+enum Child {
+    Element(usize),
+    /// Marked content in the content stream of the page or in a form XObject.
+    MarkedContent {
+        mcid: i32,
+        stream: Option<Ref>,
+    },
+}
+
+/// The marked content of a content stream in a tagged document.
+#[derive(Clone, Eq, PartialEq)]
+pub struct StreamTags {
+    /// The form XObject the stream belongs to, or `None` for the content stream of
+    /// the page.
+    stream: Option<Ref>,
+    /// The element that each marked-content id in the stream belongs to.
+    parents: Vec<usize>,
+}
+
+/// The size of the structure tree at some point, which it can be reset to.
+#[derive(Copy, Clone)]
+pub struct Checkpoint {
+    elements: usize,
+    roots: usize,
+    parent_tree: usize,
+}
+
+impl StreamTags {
+    pub fn new(stream: Option<Ref>) -> Self {
+        Self { stream, parents: vec![] }
+    }
+}
+
+impl Tags {
+    /// Add a structure element. Returns its index.
+    pub fn push(
+        &mut self,
+        role: StructRole,
+        parent: Option<usize>,
+        alt: Option<String>,
+        actual_text: Option<String>,
+    ) -> usize {
+        let index = self.elements.len();
+        self.elements.push(Element {
+            role,
+            parent,
+            page: self.page,
+            alt,
+            actual_text,
+            children: vec![],
+        });
+
+        match parent {
+            Some(parent) => self.elements[parent].children.push(Child::Element(index)),
+            None => self.roots.push(index),
+        }
+
+        index
+    }
+
+    /// Remember the current size of the structure tree.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            elements: self.elements.len(),
+            roots: self.roots.len(),
+            parent_tree: self.parent_tree.len(),
+        }
+    }
+
+    /// Remove all elements and streams that were added since the checkpoint.
+    pub fn reset(&mut self, checkpoint: Checkpoint) {
+        self.elements.truncate(checkpoint.elements);
+        self.roots.truncate(checkpoint.roots);
+        self.parent_tree.truncate(checkpoint.parent_tree);
+        for element in &mut self.elements {
+            element.children.retain(
+                |child| !matches!(child, Child::Element(i) if *i >= checkpoint.elements),
+            );
+        }
+    }
+
+    /// Begin a marked-content sequence that belongs to an element.
+    pub fn begin_marked_content(
+        &mut self,
+        content: &mut Content,
+        stream: &mut StreamTags,
+        element: usize,
+        tag: Name,
+    ) {
+        let mcid = stream.parents.len() as i32;
+        stream.parents.push(element);
+        self.elements[element]
+            .children
+            .push(Child::MarkedContent { mcid, stream: stream.stream });
+        content
+            .begin_marked_content_with_properties(tag)
+            .properties()
+            .identify(mcid);
+    }
+
+    /// Register a finished stream. Returns its key in the parent tree if it has any
+    /// marked content.
+    pub fn finish_stream(&mut self, stream: StreamTags) -> Option<i32> {
+        if stream.parents.is_empty() {
+            return None;
+        }
+
+        self.parent_tree.push(stream.parents);
+        Some(self.parent_tree.len() as i32 - 1)
+    }
+
+    /// Write the structure tree.
+    pub fn write(&self, chunk: &mut Chunk, root_ref: Ref, alloc: &mut RefAllocator) {
+        let document_ref = alloc.alloc_ref();
+        let refs: Vec<Ref> = self.elements.iter().map(|_| alloc.alloc_ref()).collect();
+
+        let mut root = chunk.indirect(root_ref).start::<StructTreeRoot>();
+        root.child(document_ref);
+        let mut nums = root.insert(Name(b"ParentTree")).dict();
+        let mut array = nums.insert(Name(b"Nums")).array();
+        for (key, parents) in self.parent_tree.iter().enumerate() {
+            array.item(key as i32);
+            array.push().array().items(parents.iter().map(|&i| refs[i]));
+        }
+        array.finish();
+        nums.finish();
+        root.parent_tree_next_key(self.parent_tree.len() as i32);
+        root.finish();
+
+        let mut document = chunk.struct_element(document_ref);
+        document.kind(StructRole::Document).parent(root_ref);
+        let mut children = document.children();
+        for &i in &self.roots {
+            children.struct_element(refs[i]);
+        }
+        children.finish();
+        document.finish();
+
+        for (element, &reference) in self.elements.iter().zip(&refs) {
+            let mut writer = chunk.struct_element(reference);
+            writer
+                .kind(element.role)
+                .parent(element.parent.map_or(document_ref, |i| refs[i]));
+            if let Some(page) = element.page {
+                writer.page(page);
+            }
+            if let Some(alt) = &element.alt {
+                writer.alt(TextStr(alt));
+            }
+            if let Some(actual_text) = &element.actual_text {
+                writer.actual_text(TextStr(actual_text));
+            }
+            write_children(&mut writer, element, &refs);
+        }
+    }
+}
+
+/// Render content as a structure element of its own, if the stream is tagged. The
+/// content itself is not tagged any further.
+pub fn render_element(
+    content: &mut Content,
+    ctx: &mut Context,
+    rc: &mut ResourceContainer,
+    (role, tag): (StructRole, Name),
+    alt: Option<String>,
+    actual_text: Option<String>,
+    render: impl FnOnce(&mut Content, &mut Context, &mut ResourceContainer) -> Result<()>,
+) -> Result<()> {
+    let Some(mut stream) = rc.tags.take() else {
+        return render(content, ctx, rc);
+    };
+
+    let element = ctx.tags.push(role, None, alt, actual_text);
+    ctx.tags.begin_marked_content(content, &mut stream, element, tag);
+    let result = render(content, ctx, rc);
+    content.end_marked_content();
+    rc.tags = Some(stream);
+    result
+}
+
+/// Render content as an artifact, if the stream is tagged.
+pub fn render_artifact(
+    content: &mut Content,
+    rc: &mut ResourceContainer,
+    render: impl FnOnce(&mut Content, &mut ResourceContainer) -> Result<()>,
+) -> Result<()> {
+    let Some(stream) = rc.tags.take() else {
+        return render(content, rc);
+    };
+
+    content.begin_marked_content(Name(b"Artifact"));
+    let result = render(content, rc);
+    content.end_marked_content();
+    rc.tags = Some(stream);
+    result
+}
+
+fn write_children(writer: &mut StructElement, element: &Element, refs: &[Ref]) {
+    let mut children = writer.children();
+    for child in &element.children {
+        match *child {
+            Child::Element(i) => {
+                children.struct_element(refs[i]);
+            }
+            Child::MarkedContent { mcid, stream: None } => {
+                children.marked_content_id(mcid);
+            }
+            Child::MarkedContent { mcid, stream: Some(stream) } => {
+                let mut reference = children.marked_content_ref();
+                reference.marked_content_id(mcid).stream(stream);
+                if let Some(page) = element.page {
+                    reference.page(page);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+#!/usr/bin/env python3
+
+"""Generates a tiny CMYK printer profile with a naive color conversion, which is
+used to test the conversion into a CMYK output profile."""
+
+import struct
+
+from common import ROOT
+
+OUT_PATH = ROOT / "icc" / "naive-cmyk.icc"
+
+# The D50 white point of the profile connection space.
+D50 = (0.9642, 1.0, 0.8249)
+
+# The number of grid points per input channel of the Lab to CMYK lookup table.
+GRID_POINTS = 9
+
+IDENTITY_MATRIX = [1, 0, 0, 0, 1, 0, 0, 0, 1]
+IDENTITY_CURVE = bytes(range(256))
+
+
+def s15_fixed16(value: float) -> bytes:
+    """Encodes a number as an ICC s15Fixed16Number."""
+    return struct.pack(">i", round(value * 65536))
+
+
+def xyz(values) -> bytes:
+    return b"".join(s15_fixed16(value) for value in values)
+
+
+def lab_to_srgb(lightness: float, a: float, b: float):
+    """Converts a D50 Lab color into gamma-encoded sRGB."""
+
+    def f_inverse(t: float) -> float:
+        if t**3 > 0.008856:
+            return t**3
+        return (t - 16 / 116) / 7.787
+
+    fy = (lightness + 16) / 116
+    fx = fy + a / 500
+    fz = fy - b / 200
+    x, y, z = (white * f_inverse(t) for white, t in zip(D50, (fx, fy, fz)))
+
+    # D50 XYZ to linear sRGB, with Bradford chromatic adaptation.
+    red = 3.1338561 * x - 1.6168667 * y - 0.4906146 * z
+    green = -0.9787684 * x + 1.9161415 * y + 0.0334540 * z
+    blue = 0.0719453 * x - 0.2289914 * y + 1.4052427 * z
+
+    def gamma(channel: float) -> float:
+        channel = min(max(channel, 0), 1)
+        if channel <= 0.0031308:
+            return 12.92 * channel
+        return 1.055 * channel ** (1 / 2.4) - 0.055
+
+    return gamma(red), gamma(green), gamma(blue)
+
+
+def rgb_to_cmyk(red: float, green: float, blue: float):
+    """The naive conversion from RGB into CMYK with full black generation."""
+    black = 1 - max(red, green, blue)
+    if black >= 1:
+        return 0, 0, 0, 1
+
+    return (
+        (1 - red - black) / (1 - black),
+        (1 - green - black) / (1 - black),
+        (1 - blue - black) / (1 - black),
+        black,
+    )
+
+
+def lut8(inputs: int, outputs: int, grid_points: int, table: bytes) -> bytes:
+    """Writes an 8-bit lookup table tag with identity matrix and curves."""
+    return (
+        b"mft1"
+        + bytes(4)
+        + bytes([inputs, outputs, grid_points, 0])
+        + xyz(IDENTITY_MATRIX)
+        + IDENTITY_CURVE * inputs
+        + table
+        + IDENTITY_CURVE * outputs
+    )
+
+
+def lab_to_cmyk_tag() -> bytes:
+    """The B2A0 tag, which converts Lab into CMYK."""
+    table = b""
+    for i in range(GRID_POINTS):
+        for j in range(GRID_POINTS):
+            for k in range(GRID_POINTS):
+                lightness = i / (GRID_POINTS - 1) * 100
+                a = j / (GRID_POINTS - 1) * 255 - 128
+                b = k / (GRID_POINTS - 1) * 255 - 128
+                cmyk = rgb_to_cmyk(*lab_to_srgb(lightness, a, b))
+                table += bytes(round(min(max(v, 0), 1) * 255) for v in cmyk)
+
+    return lut8(3, 4, GRID_POINTS, table)
+
+
+def cmyk_to_lab_tag() -> bytes:
+    """The A2B0 tag, which converts CMYK into Lab with a grid of two points per
+    channel. Only the lightness is approximated, all colors are gray."""
+    table = b""
+    for c in (0, 1):
+        for m in (0, 1):
+            for y in (0, 1):
+                for k in (0, 1):
+                    lightness = 100 * (1 - k) * (1 - 0.5 * max(c, m, y))
+                    table += bytes([round(lightness / 100 * 255), 128, 128])
+
+    return lut8(4, 3, 2, table)
+
+
+def description_tag(description: bytes) -> bytes:
+    """A textDescriptionType tag without Unicode and ScriptCode descriptions."""
+    ascii_description = description + b"\0"
+    return (
+        b"desc"
+        + bytes(4)
+        + struct.pack(">I", len(ascii_description))
+        + ascii_description
+        # Unicode language code and length.
+        + bytes(8)
+        # ScriptCode code, length and description.
+        + bytes(3)
+        + bytes(67)
+    )
+
+
+def header(size: int) -> bytes:
+    result = (
+        struct.pack(">I", size)
+        # Preferred CMM.
+        + b"    "
+        # Version 2.1.
+        + struct.pack(">I", 0x02100000)
+        + b"prtr"
+        + b"CMYK"
+        + b"Lab "
+        # Creation date.
+        + bytes(12)
+        + b"acsp"
+        # Platform, flags, manufacturer and model, attributes.
+        + bytes(4 + 4 + 8 + 8)
+        # Rendering intent.
+        + struct.pack(">I", 0)
+        + xyz(D50)
+        # Creator, reserved.
+        + bytes(4 + 16 + 28)
+    )
+    assert len(result) == 128
+    return result
+
+
+def main():
+    tags = [
+        (b"A2B0", cmyk_to_lab_tag()),
+        (b"B2A0", lab_to_cmyk_tag()),
+        (b"wtpt", b"XYZ " + bytes(4) + xyz(D50)),
+        (b"desc", description_tag(b"Naive CMYK")),
+    ]
+
+    # The tag data starts after the header and the tag table and each tag is
+    # aligned to four bytes.
+    offset = 128 + 4 + 12 * len(tags)
+    table = struct.pack(">I", len(tags))
+    data = b""
+    for signature, tag in tags:
+        while (offset + len(data)) % 4:
+            data += b"\0"
+        table += signature + struct.pack(">II", offset + len(data), len(tag))
+        data += tag
+
+    body = table + data
+    OUT_PATH.write_bytes(header(128 + len(body)) + body)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,144 @@
+#!/usr/bin/env python3
+
+"""Generates a tiny TrueType font with one glyph for each kind of color glyph
+table, which is used to test the conversion of color glyphs."""
+
+import struct
+import zlib
+
+import sfnt
+from common import ROOT
+
+OUT_PATH = ROOT / "fonts" / "ColorTest-Regular.ttf"
+
+FAMILY = "Color Test"
+
+UNITS_PER_EM = 1000
+ADVANCE = 600
+
+# The glyphs of the font, by their id.
+NOTDEF, COLR_GLYPH, SVG_GLYPH, SBIX_GLYPH, PLAIN_GLYPH, SQUARE, TRIANGLE = range(7)
+NUM_GLYPHS = 7
+
+# The characters that are mapped to glyphs.
+CMAP = {
+    "A": COLR_GLYPH,
+    "B": SVG_GLYPH,
+    "C": SBIX_GLYPH,
+    "D": PLAIN_GLYPH,
+}
+
+# The outlines of the glyphs, as a single closed contour of on-curve points.
+# Colored glyphs have an outline too, which is used by renderers without support
+# for color glyphs.
+FULL = [(50, 0), (50, 700), (550, 700), (550, 0)]
+OUTLINES = {
+    NOTDEF: [],
+    COLR_GLYPH: FULL,
+    SVG_GLYPH: FULL,
+    SBIX_GLYPH: FULL,
+    PLAIN_GLYPH: [(100, 0), (100, 500), (500, 500), (500, 0)],
+    SQUARE: [(50, 0), (50, 500), (350, 500), (350, 0)],
+    TRIANGLE: [(250, 200), (400, 700), (550, 200)],
+}
+
+
+def cpal() -> bytes:
+    # One palette with a single entry: Red with half opacity, in BGRA.
+    return struct.pack(">HHHHIH", 0, 1, 1, 1, 14, 0) + bytes([0, 0, 255, 128])
+
+
+def colr() -> bytes:
+    # The COLR glyph consists of a red square and a triangle in the foreground
+    # color.
+    base_glyph = struct.pack(">HHH", COLR_GLYPH, 0, 2)
+    layers = struct.pack(">HHHH", SQUARE, 0, TRIANGLE, 0xFFFF)
+    return struct.pack(">HHIIH", 0, 1, 14, 14 + len(base_glyph), 2) + base_glyph + layers
+
+
+def svg() -> bytes:
+    document = (
+        '<svg xmlns="http://www.w3.org/2000/svg">'
+        '<defs><linearGradient id="gradient">'
+        '<stop offset="0" stop-color="blue"/><stop offset="1" stop-color="green"/>'
+        "</linearGradient></defs>"
+        f'<g id="glyph{SVG_GLYPH}">'
+        '<rect x="50" y="-700" width="500" height="700" fill="url(#gradient)"/>'
+        "</g></svg>"
+    ).encode()
+
+    document_list = struct.pack(">H", 1)
+    document_list += struct.pack(">HHII", SVG_GLYPH, SVG_GLYPH, 2 + 12, len(document))
+    document_list += document
+    return struct.pack(">HII", 0, 10, 0) + document_list
+
+
+def png(width: int, height: int, rows) -> bytes:
+    """Writes an RGBA PNG."""
+
+    def chunk(kind: bytes, data: bytes) -> bytes:
+        checksum = zlib.crc32(kind + data) & 0xFFFFFFFF
+        return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", checksum)
+
+    header = struct.pack(">IIBBBBB", width, height, 8, 6, 0, 0, 0)
+    data = zlib.compress(b"".join(b"\0" + row for row in rows))
+    return (
+        b"\x89PNG\r\n\x1a\n"
+        + chunk(b"IHDR", header)
+        + chunk(b"IDAT", data)
+        + chunk(b"IEND", b"")
+    )
+
+
+def sbix() -> bytes:
+    # A 2x2 image with a red, a green, a blue and a half transparent yellow pixel.
+    image = png(
+        2,
+        2,
+        [
+            bytes([255, 0, 0, 255, 0, 255, 0, 255]),
+            bytes([0, 0, 255, 255, 255, 255, 0, 128]),
+        ],
+    )
+    glyph_data = struct.pack(">hh4s", 0, 0, b"png ") + image
+
+    # A single strike for 20 pixels per em, in which only the sbix glyph has data.
+    header_size = 4 + 4 * (NUM_GLYPHS + 1)
+    offsets = []
+    data = b""
+    for glyph in range(NUM_GLYPHS):
+        offsets.append(header_size + len(data))
+        if glyph == SBIX_GLYPH:
+            data += glyph_data
+    offsets.append(header_size + len(data))
+
+    strike = struct.pack(">HH", 20, 72)
+    strike += struct.pack(f">{len(offsets)}I", *offsets)
+    strike += data
+    return struct.pack(">HHII", 1, 1, 1, 12) + strike
+
+
+def main():
+    outlines = [OUTLINES[glyph] for glyph in range(NUM_GLYPHS)]
+    glyf, loca = sfnt.glyf_and_loca(outlines)
+    tables = {
+        b"COLR": colr(),
+        b"CPAL": cpal(),
+        b"SVG ": svg(),
+        b"cmap": sfnt.cmap(CMAP),
+        b"glyf": glyf,
+        b"head": sfnt.head(UNITS_PER_EM, (0, 0, 550, 700)),
+        b"hhea": sfnt.hhea(ADVANCE, 550, NUM_GLYPHS),
+        b"hmtx": sfnt.hmtx([ADVANCE] * NUM_GLYPHS, outlines),
+        b"loca": loca,
+        b"maxp": sfnt.maxp(NUM_GLYPHS),
+        b"name": sfnt.name(FAMILY),
+        b"post": sfnt.post(),
+        b"sbix": sbix(),
+    }
+
+    OUT_PATH.write_bytes(sfnt.font(tables))
+
+
+if __name__ == "__main__":
+    main()
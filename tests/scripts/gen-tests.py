@@ -9,22 +9,33 @@ OUT_PATH = ROOT / "src" / "render.rs"
 
 NO_RELATIVE_PATHS = "no relative paths supported"
 INVESTIGATE = "need to investigate"
-NO_REFLECT = "spreadMethod reflect not supported"
-NO_REPEAT = "spreadMethod repeat not supported"
 NO_SUPPORT = "not supported in PDF"
 NO_FONT = "font is not part of test suite yet"
+NO_REFERENCE = "reference image is not part of test suite yet"
 
 IGNORE_TESTS = {
     # The following test cases still need to be investigated
     "svg/resvg/painting/stroke-dasharray/n-0.svg": INVESTIGATE,
-    "svg/resvg/text/text/compound-emojis.svg": INVESTIGATE,
-    "svg/resvg/text/text/compound-emojis-and-coordinates-list.svg": INVESTIGATE,
-    "svg/resvg/text/text/emojis.svg": INVESTIGATE,
+
+    # The following test cases need a color emoji font
+    "svg/resvg/text/text/compound-emojis.svg": NO_FONT,
+    "svg/resvg/text/text/compound-emojis-and-coordinates-list.svg": NO_FONT,
+    "svg/resvg/text/text/emojis.svg": NO_FONT,
+
+    # The following test cases still need a reference image
+    "svg/resvg/paint-servers/linearGradient/attributes-via-xlink-href-complex-order.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/linearGradient/attributes-via-xlink-href-from-radialGradient.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/linearGradient/spreadMethod=reflect.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/linearGradient/spreadMethod=repeat.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/radialGradient/attributes-via-xlink-href-complex-order.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/radialGradient/attributes-via-xlink-href-from-linearGradient.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/radialGradient/spreadMethod=reflect.svg": NO_REFERENCE,
+    "svg/resvg/paint-servers/radialGradient/spreadMethod=repeat.svg": NO_REFERENCE,
+    "svg/resvg/filters/filter/huge-region.svg": NO_REFERENCE,
 
     # The following test cases need to be excluded due to technical reasons
     # and are not considered as wrong.
     "svg/resvg/filters/feMorphology/huge-radius.svg": "will timeout CI",
-    "svg/resvg/filters/filter/huge-region.svg": "will sigkill",
     "svg/resvg/structure/svg/negative-size.svg": "invalid size",
     "svg/resvg/structure/svg/no-size.svg": "invalid size",
     "svg/resvg/structure/svg/zero-size.svg": "invalid size",
@@ -55,14 +66,6 @@ IGNORE_TESTS = {
     "svg/resvg/structure/image/zero-width.svg": NO_RELATIVE_PATHS,
 
     # The following test cases should work but are not implemented in svg2pdf yet.
-    "svg/resvg/paint-servers/linearGradient/attributes-via-xlink-href-complex-order.svg": NO_REFLECT,
-    "svg/resvg/paint-servers/linearGradient/attributes-via-xlink-href-from-radialGradient.svg": NO_REFLECT,
-    "svg/resvg/paint-servers/linearGradient/spreadMethod=reflect.svg": NO_REFLECT,
-    "svg/resvg/paint-servers/linearGradient/spreadMethod=repeat.svg": NO_REPEAT,
-    "svg/resvg/paint-servers/radialGradient/attributes-via-xlink-href-complex-order.svg":NO_REFLECT,
-    "svg/resvg/paint-servers/radialGradient/attributes-via-xlink-href-from-linearGradient.svg": NO_REFLECT,
-    "svg/resvg/paint-servers/radialGradient/spreadMethod=reflect.svg": NO_REFLECT,
-    "svg/resvg/paint-servers/radialGradient/spreadMethod=repeat.svg": NO_REPEAT,
     "svg/resvg/painting/stroke-linecap/zero-length-path-with-round.svg": "need to check how Chrome does it",
     "svg/resvg/painting/stroke-linecap/zero-length-path-with-square.svg": "need to check how Firefox does it",
 }
@@ -0,0 +1,227 @@
+#!/usr/bin/env python3
+
+"""Generates two tiny variable fonts, one with TrueType and one with CFF2 outlines,
+with a weight axis that widens their glyph, which are used to test that the default
+instance of variable fonts is used."""
+
+import struct
+
+import sfnt
+from common import ROOT
+
+OUT_PATH = ROOT / "fonts" / "VariableTest-Regular.ttf"
+CFF2_OUT_PATH = ROOT / "fonts" / "VariableCFF2Test-Regular.otf"
+
+FAMILY = "Variable Test"
+CFF2_FAMILY = "Variable CFF2 Test"
+
+UNITS_PER_EM = 1000
+ADVANCE = 600
+
+# The glyphs of the font, by their id.
+NOTDEF, WIDE_GLYPH = range(2)
+NUM_GLYPHS = 2
+
+CMAP = {"A": WIDE_GLYPH}
+
+OUTLINES = [[], [(50, 0), (50, 700), (550, 700), (550, 0)]]
+
+# The weight axis, with its minimum, default and maximum value.
+WEIGHT_AXIS = (100, 400, 900)
+WEIGHT_AXIS_NAME_ID = 256
+
+# How far the right edge and the advance of the glyph move at the maximum weight.
+WIDENING = 200
+
+
+def fixed(value: float) -> int:
+    return round(value * 65536)
+
+
+def fvar() -> bytes:
+    # A single axis without named instances.
+    header = struct.pack(">HHHHHHHH", 1, 0, 16, 2, 1, 20, 0, 8)
+    axis = struct.pack(
+        ">4siiiHH",
+        b"wght",
+        *(fixed(value) for value in WEIGHT_AXIS),
+        0,
+        WEIGHT_AXIS_NAME_ID,
+    )
+    return header + axis
+
+
+def widening_deltas(points):
+    """The x deltas of the points of an outline at the maximum weight, which move
+    its right edge."""
+    return [WIDENING if x == 550 else 0 for x, _ in points]
+
+
+def glyph_variations() -> bytes:
+    """The variation data of the wide glyph: A single tuple whose peak is at the
+    maximum weight and which moves the right edge and the advance to the right."""
+    # Use all points of the glyph, including the four phantom points.
+    point_numbers = bytes([0])
+    x_deltas = widening_deltas(OUTLINES[WIDE_GLYPH])
+    # The phantom points are the origin, the advance, the top and the bottom.
+    x_deltas += [0, WIDENING, 0, 0]
+
+    # One run of 16-bit x deltas and one run of zero y deltas.
+    data = point_numbers
+    data += bytes([0x40 | (len(x_deltas) - 1)])
+    data += struct.pack(f">{len(x_deltas)}h", *x_deltas)
+    data += bytes([0x80 | (len(x_deltas) - 1)])
+
+    # The tuple has an embedded peak and its own point numbers.
+    header = struct.pack(">HHh", len(data), 0x8000 | 0x2000, 0x4000)
+    result = struct.pack(">HH", 1, 4 + len(header)) + header + data
+    return result + bytes(-len(result) % 2)
+
+
+def gvar() -> bytes:
+    variations = [b"", glyph_variations()]
+
+    offsets = [0]
+    for data in variations:
+        offsets.append(offsets[-1] + len(data))
+
+    # The header is followed by the short offsets to the variation data of the
+    # glyphs. There are no shared tuples.
+    data_offset = 20 + 2 * len(offsets)
+    header = struct.pack(">HHHHIHHI", 1, 0, 1, 0, data_offset, NUM_GLYPHS, 0, data_offset)
+    header += struct.pack(f">{len(offsets)}H", *(offset // 2 for offset in offsets))
+    return header + b"".join(variations)
+
+
+def variation_store(item_data: bytes) -> bytes:
+    """An item variation store with a single region, which has the same peak as
+    the tuple in `gvar`, and a single item variation data subtable."""
+    regions = struct.pack(">HHhhh", 1, 1, 0, 0x4000, 0x4000)
+    header = struct.pack(">HIHI", 1, 12, 1, 12 + len(regions))
+    return header + regions + item_data
+
+
+def hvar() -> bytes:
+    """Varies the advance of the wide glyph like its phantom point in `gvar`, since
+    some font parsers only take advances from here."""
+    # One 16-bit delta per glyph, which are mapped implicitly by their id.
+    deltas = [WIDENING if glyph == WIDE_GLYPH else 0 for glyph in range(NUM_GLYPHS)]
+    item_data = struct.pack(">HHHH", NUM_GLYPHS, 1, 1, 0)
+    item_data += struct.pack(f">{NUM_GLYPHS}h", *deltas)
+
+    return struct.pack(">HHIIII", 1, 0, 20, 0, 0, 0) + variation_store(item_data)
+
+
+def cff2_index(items) -> bytes:
+    """Writes a CFF2 INDEX, whose count has 32 bits, with one-byte offsets."""
+    if not items:
+        return struct.pack(">I", 0)
+
+    offsets = [1]
+    for item in items:
+        offsets.append(offsets[-1] + len(item))
+
+    result = struct.pack(">IB", len(items), 1) + bytes(offsets)
+    return result + b"".join(items)
+
+
+def operand(value: int) -> bytes:
+    """An integer operand, which may only have 32 bits in dicts."""
+    if -(2**15) <= value < 2**15:
+        return struct.pack(">Bh", 28, value)
+    return struct.pack(">Bi", 29, value)
+
+
+def dict_offset(value: int) -> bytes:
+    """An integer operand of a dict that always takes five bytes, so that the
+    length of a dict doesn't depend on the offsets in it."""
+    return struct.pack(">Bi", 29, value)
+
+
+def char_string(points) -> bytes:
+    """Writes a closed outline as a CFF2 charstring, whose points are moved like the
+    ones of the wide glyph in `gvar` by blending their relative coordinates."""
+    if not points:
+        return b""
+
+    result = b""
+    previous = (0, 0, 0)
+    for i, ((x, y), x_delta) in enumerate(zip(points, widening_deltas(points))):
+        result += operand(x - previous[0])
+        if x_delta != previous[2]:
+            # One blended operand with a delta for the single region.
+            result += operand(x_delta - previous[2]) + operand(1) + bytes([16])
+        # rmoveto for the first point and rlineto for all others.
+        result += operand(y - previous[1]) + bytes([5 if i > 0 else 21])
+        previous = (x, y, x_delta)
+
+    return result
+
+
+def cff2() -> bytes:
+    # The variation store has no items, its regions are only referenced by the
+    # blend operators.
+    store = variation_store(struct.pack(">HHHH", 0, 0, 1, 0))
+    store = struct.pack(">H", len(store)) + store
+    char_strings = cff2_index([char_string(points) for points in OUTLINES])
+
+    def font_dict(private_offset):
+        # A single font dict with an empty private dict.
+        return operand(0) + dict_offset(private_offset) + bytes([18])
+
+    def top_dict(char_strings_offset, store_offset, font_dicts_offset):
+        result = dict_offset(char_strings_offset) + bytes([17])
+        result += dict_offset(store_offset) + bytes([24])
+        return result + dict_offset(font_dicts_offset) + bytes([12, 36])
+
+    # The header, the top dict and the empty global subroutine index are followed
+    # by the variation store, the charstrings, the font dicts and the private dict.
+    store_offset = 5 + len(top_dict(0, 0, 0)) + len(cff2_index([]))
+    char_strings_offset = store_offset + len(store)
+    font_dicts_offset = char_strings_offset + len(char_strings)
+    private_offset = font_dicts_offset + len(cff2_index([font_dict(0)]))
+
+    top = top_dict(char_strings_offset, store_offset, font_dicts_offset)
+    result = struct.pack(">BBBH", 2, 0, 5, len(top)) + top + cff2_index([])
+    result += store + char_strings + cff2_index([font_dict(private_offset)])
+    return result
+
+
+def main():
+    glyf, loca = sfnt.glyf_and_loca(OUTLINES)
+    tables = {
+        b"cmap": sfnt.cmap(CMAP),
+        b"fvar": fvar(),
+        b"glyf": glyf,
+        b"gvar": gvar(),
+        b"HVAR": hvar(),
+        b"head": sfnt.head(UNITS_PER_EM, (0, 0, 550, 700)),
+        b"hhea": sfnt.hhea(ADVANCE, 550, NUM_GLYPHS),
+        b"hmtx": sfnt.hmtx([ADVANCE] * NUM_GLYPHS, OUTLINES),
+        b"loca": loca,
+        b"maxp": sfnt.maxp(NUM_GLYPHS),
+        b"name": sfnt.name(FAMILY, {WEIGHT_AXIS_NAME_ID: "Weight"}),
+        b"post": sfnt.post(),
+    }
+
+    OUT_PATH.write_bytes(sfnt.font(tables))
+
+    cff2_tables = {
+        b"cmap": sfnt.cmap(CMAP),
+        b"CFF2": cff2(),
+        b"fvar": fvar(),
+        b"HVAR": hvar(),
+        b"head": sfnt.head(UNITS_PER_EM, (0, 0, 550, 700)),
+        b"hhea": sfnt.hhea(ADVANCE, 550, NUM_GLYPHS),
+        b"hmtx": sfnt.hmtx([ADVANCE] * NUM_GLYPHS, OUTLINES),
+        # Version 0.5, which only contains the number of glyphs.
+        b"maxp": struct.pack(">IH", 0x00005000, NUM_GLYPHS),
+        b"name": sfnt.name(CFF2_FAMILY, {WEIGHT_AXIS_NAME_ID: "Weight"}),
+        b"post": sfnt.post(),
+    }
+
+    CFF2_OUT_PATH.write_bytes(sfnt.font(cff2_tables, version=0x4F54544F))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,203 @@
+"""Helpers for writing the tables of the tiny TrueType fonts used in the tests."""
+
+import struct
+
+
+def simple_glyph(points) -> bytes:
+    """Writes a simple glyph with a single contour without instructions."""
+    if not points:
+        return b""
+
+    xs = [x for x, _ in points]
+    ys = [y for _, y in points]
+    result = struct.pack(">hhhhh", 1, min(xs), min(ys), max(xs), max(ys))
+    result += struct.pack(">HH", len(points) - 1, 0)
+    # All points are on the curve and have 16-bit coordinates.
+    result += bytes([0x01] * len(points))
+
+    previous = (0, 0)
+    x_deltas = b""
+    y_deltas = b""
+    for x, y in points:
+        x_deltas += struct.pack(">h", x - previous[0])
+        y_deltas += struct.pack(">h", y - previous[1])
+        previous = (x, y)
+
+    result += x_deltas + y_deltas
+    # Glyphs are aligned to four bytes so that short offsets can be used.
+    return result + bytes(-len(result) % 4)
+
+
+def glyf_and_loca(outlines):
+    """Writes the outlines of all glyphs, given as a list indexed by glyph id."""
+    glyf = b""
+    offsets = []
+    for points in outlines:
+        offsets.append(len(glyf))
+        glyf += simple_glyph(points)
+    offsets.append(len(glyf))
+
+    loca = struct.pack(f">{len(offsets)}H", *(offset // 2 for offset in offsets))
+    return glyf, loca
+
+
+def head(units_per_em: int, bbox) -> bytes:
+    return struct.pack(
+        ">IIIIHHqqhhhhHHhhh",
+        # Version and font revision.
+        0x00010000,
+        0x00010000,
+        # The checksum adjustment isn't checked by font parsers.
+        0,
+        0x5F0F3CF5,
+        # Flags.
+        0b11,
+        units_per_em,
+        # Creation and modification date.
+        0,
+        0,
+        # The bounding box of all glyphs.
+        *bbox,
+        # Mac style, smallest readable size and font direction hint.
+        0,
+        8,
+        2,
+        # Short offsets in `loca` and the current glyph data format.
+        0,
+        0,
+    )
+
+
+def hhea(max_advance: int, max_extent: int, num_glyphs: int) -> bytes:
+    return struct.pack(
+        ">IhhhHhhhhhh8xhH",
+        0x00010000,
+        # Ascender, descender and line gap.
+        800,
+        -200,
+        0,
+        max_advance,
+        # Minimum left and right side bearing and maximum extent.
+        0,
+        0,
+        max_extent,
+        # Caret slope rise and run and caret offset.
+        1,
+        0,
+        0,
+        # Metric data format and number of horizontal metrics.
+        0,
+        num_glyphs,
+    )
+
+
+def maxp(num_glyphs: int) -> bytes:
+    return struct.pack(
+        ">IHHHHHHHHHHHHHH",
+        0x00010000,
+        num_glyphs,
+        # Maximum points and contours of simple and composite glyphs.
+        4,
+        1,
+        0,
+        0,
+        # Maximum zones, twilight points, storage, function and instruction defs
+        # and stack elements.
+        2,
+        0,
+        0,
+        0,
+        0,
+        0,
+        # Maximum size of instructions, component elements and component depth.
+        0,
+        0,
+        0,
+    )
+
+
+def hmtx(advances, outlines) -> bytes:
+    result = b""
+    for advance, points in zip(advances, outlines):
+        left_side_bearing = min(x for x, _ in points) if points else 0
+        result += struct.pack(">Hh", advance, left_side_bearing)
+    return result
+
+
+def cmap(mapping) -> bytes:
+    """A format 4 subtable with one segment per character."""
+    chars = sorted(mapping)
+    # The last segment has to map 0xFFFF.
+    end_codes = [ord(char) for char in chars] + [0xFFFF]
+    start_codes = end_codes
+    deltas = [mapping[char] - ord(char) for char in chars] + [1]
+
+    segments = len(end_codes)
+    search_range = 2 ** (segments.bit_length() - 1) * 2
+    entry_selector = segments.bit_length() - 1
+    range_shift = segments * 2 - search_range
+
+    subtable = struct.pack(
+        ">HHHHHH", 4, 0, 0, segments * 2, search_range, entry_selector
+    )
+    subtable += struct.pack(">H", range_shift)
+    subtable += struct.pack(f">{segments}H", *end_codes)
+    subtable += struct.pack(">H", 0)
+    subtable += struct.pack(f">{segments}H", *start_codes)
+    subtable += struct.pack(f">{segments}h", *deltas)
+    subtable += struct.pack(f">{segments}H", *([0] * segments))
+    subtable = subtable[:2] + struct.pack(">H", len(subtable)) + subtable[4:]
+
+    # A single subtable for Unicode BMP on the Windows platform.
+    return struct.pack(">HHHHI", 0, 1, 3, 1, 12) + subtable
+
+
+def name(family: str, extra=None) -> bytes:
+    """Writes the names of a regular font, followed by any extra names, given as
+    a dictionary from name id to value."""
+    names = {
+        1: family,
+        2: "Regular",
+        4: f"{family} Regular",
+        6: family.replace(" ", "") + "-Regular",
+        **(extra or {}),
+    }
+
+    records = b""
+    strings = b""
+    for name_id, value in names.items():
+        encoded = value.encode("utf-16-be")
+        # Windows platform, Unicode BMP encoding and English language.
+        records += struct.pack(">HHHHHH", 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+
+    header = struct.pack(">HHH", 0, len(names), 6 + len(records))
+    return header + records + strings
+
+
+def post() -> bytes:
+    # Version 3 without glyph names.
+    return struct.pack(">IIhhIIIII", 0x00030000, 0, -100, 50, 0, 0, 0, 0, 0)
+
+
+def font(tables, version=0x00010000) -> bytes:
+    """Writes a font file from a dictionary of table tags to table data. The
+    version is the one of TrueType outlines by default and `OTTO` for CFF ones."""
+    # The table data starts after the offset table and the table records and
+    # each table is aligned to four bytes.
+    tags = sorted(tables)
+    search_range = 2 ** (len(tags).bit_length() - 1) * 16
+    entry_selector = len(tags).bit_length() - 1
+    range_shift = len(tags) * 16 - search_range
+    directory = struct.pack(
+        ">IHHHH", version, len(tags), search_range, entry_selector, range_shift
+    )
+
+    offset = 12 + 16 * len(tags)
+    data = b""
+    for tag in tags:
+        table = tables[tag]
+        directory += struct.pack(">4sIII", tag, 0, offset + len(data), len(table))
+        data += table + bytes(-len(table) % 4)
+
+    return directory + data
@@ -1,12 +1,13 @@
 #[allow(unused_imports)]
 use {
+    crate::{contains, convert_tree, convert_with, count, run_svg_test},
     crate::{convert_svg, run_test_impl},
-    crate::{render_pdf, FONTDB},
+    crate::{read_svg, render_pdf, FONTDB, PDFIUM},
     pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str},
     std::collections::HashMap,
     std::path::Path,
-    svg2pdf::ConversionOptions,
     svg2pdf::PageOptions,
+    svg2pdf::{ConversionError, ConversionOptions, Metadata},
 };
 
 #[test]
@@ -51,7 +52,7 @@ fn to_chunk() {
         svg2pdf::usvg::Tree::from_str(&svg, &svg2pdf::usvg::Options::default(), &db)
             .unwrap();
     let (svg_chunk, svg_id) =
-        svg2pdf::to_chunk(&tree, svg2pdf::ConversionOptions::default(), &db);
+        svg2pdf::to_chunk(&tree, svg2pdf::ConversionOptions::default(), &db).unwrap();
 
     let mut map = HashMap::new();
     let svg_chunk =
@@ -97,3 +98,774 @@ fn to_chunk() {
 
     assert_eq!(res, 0);
 }
+
+#[test]
+fn multi_page() {
+    let paths = [
+        "svg/custom/integration/matplotlib/stairs.svg",
+        "svg/custom/integration/matplotlib/signals.svg",
+        "svg/resvg/text/text/simple-case.svg",
+    ];
+    let trees: Vec<_> = paths
+        .iter()
+        .map(|p| read_svg(&std::fs::read_to_string(p).unwrap()))
+        .collect();
+    let pages: Vec<_> = trees.iter().map(|t| (t, PageOptions::default())).collect();
+
+    let pdf = svg2pdf::to_multi_page_pdf(
+        &pages,
+        ConversionOptions::default(),
+        &FONTDB.lock().unwrap(),
+    )
+    .unwrap();
+
+    let pdfium = PDFIUM.lock().unwrap();
+    let document = pdfium.load_pdf_from_byte_slice(&pdf, None).unwrap();
+    assert_eq!(document.pages().len(), 3);
+}
+
+#[test]
+fn metadata_from_svg() {
+    let svg = std::fs::read_to_string(
+        "svg/resvg/paint-servers/linearGradient/spreadMethod=reflect.svg",
+    )
+    .unwrap();
+    let metadata = Metadata::from_svg(&svg);
+    assert_eq!(metadata.title.as_deref(), Some("spreadMethod=reflect"));
+    assert_eq!(metadata.language, None);
+
+    // Fields that are already set are kept.
+    let mut metadata = Metadata {
+        title: Some("Gradient".into()),
+        author: Some("Jane".into()),
+        ..Metadata::default()
+    };
+    metadata.fill_from_svg(&svg);
+    assert_eq!(metadata.title.as_deref(), Some("Gradient"));
+    assert_eq!(metadata.author.as_deref(), Some("Jane"));
+
+    let mut metadata = Metadata { author: Some("Jane".into()), ..Metadata::default() };
+    metadata.fill_from_svg(&svg);
+    assert_eq!(metadata.title.as_deref(), Some("spreadMethod=reflect"));
+    assert_eq!(metadata.author.as_deref(), Some("Jane"));
+}
+
+#[test]
+fn pdfa_page_size() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20000" height="100"/>"#;
+    let options = ConversionOptions { pdfa: true, ..ConversionOptions::default() };
+
+    let result = convert_tree(&read_svg(svg), options);
+    assert!(matches!(result, Err(ConversionError::PdfA(_))));
+}
+
+#[test]
+fn corrupt_image() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="50" height="50" href="data:image/png;base64,iVBORw0KGgpnYXJiYWdlZ2FyYmFnZWdhcmJhZ2U="/>
+        <rect width="50" height="50" fill="blue"/>
+    </svg>"#;
+    let tree = read_svg(svg);
+
+    let result = convert_tree(&tree, ConversionOptions::default());
+    assert!(matches!(result, Err(ConversionError::InvalidImage(_))));
+
+    let options = ConversionOptions { lenient: true, ..ConversionOptions::default() };
+    assert!(convert_tree(&tree, options).is_ok());
+}
+
+#[test]
+fn lenient_skips_without_traces() {
+    let svg = |image: &str| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                <g opacity="0.5">
+                    <rect width="50" height="50" fill="blue"/>
+                    {image}
+                </g>
+            </svg>"#
+        )
+    };
+    let corrupt = r#"<image id="broken" width="50" height="50" href="data:image/png;base64,iVBORw0KGgpnYXJiYWdlZ2FyYmFnZWdhcmJhZ2U="/>"#;
+
+    // A skipped image leaves neither operators nor resources, objects or structure
+    // elements behind, even though its figure was already started.
+    let convert = |svg: &str| {
+        let options = ConversionOptions {
+            lenient: true,
+            tagged: true,
+            compress: false,
+            alt_texts: HashMap::from([("broken".to_string(), "Broken".to_string())]),
+            ..ConversionOptions::default()
+        };
+        convert_with(svg, options)
+    };
+    assert_eq!(convert(&svg(corrupt)), convert(&svg("")));
+}
+
+#[test]
+fn zero_sized_tree() {
+    let tree =
+        read_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"/>"#);
+    let page_options = PageOptions { dpi: f32::INFINITY };
+
+    let result = svg2pdf::to_pdf(
+        &tree,
+        ConversionOptions::default(),
+        page_options,
+        &FONTDB.lock().unwrap(),
+    );
+    assert_eq!(result, Err(ConversionError::ZeroSizedTree));
+}
+
+#[test]
+fn cmyk_jpeg() {
+    // Only contains the markers of a 2x2 CMYK JPEG written by an Adobe application,
+    // since the image data itself is passed through without decoding it.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="100" height="100" href="data:image/jpeg;base64,/9j/7gAOQWRvYmUAZAAAAAAC/8AAFAgAAgACBAERAAIRAAMRAAQRAP/aAAYAAAAA/9k="/>
+    </svg>"#;
+    let tree = read_svg(svg);
+
+    let pdf = convert_tree(&tree, ConversionOptions::default()).unwrap();
+    assert!(contains(&pdf, b"/ColorSpace /DeviceCMYK"));
+    assert!(contains(&pdf, b"/Decode [1 0 1 0 1 0 1 0]"));
+    assert!(contains(&pdf, b"/ColorTransform 1"));
+
+    let options = ConversionOptions { pdfa: true, ..ConversionOptions::default() };
+    let result = convert_tree(&tree, options);
+    assert!(matches!(result, Err(ConversionError::PdfA(_))));
+
+    // Conformance violations are reported in lenient mode as well.
+    let options = ConversionOptions {
+        pdfa: true,
+        lenient: true,
+        ..ConversionOptions::default()
+    };
+    let result = convert_tree(&tree, options);
+    assert!(matches!(result, Err(ConversionError::PdfA(_))));
+}
+
+#[allow(dead_code)]
+const CMYK_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+    <linearGradient id="lg">
+        <stop offset="0" stop-color="cyan"/>
+        <stop offset="1" stop-color="yellow"/>
+    </linearGradient>
+    <mask id="m"><rect width="50" height="100" fill="white"/></mask>
+    <rect width="100" height="50" fill="red" mask="url(#m)"/>
+    <rect y="50" width="100" height="50" fill="url(#lg)"/>
+</svg>"#;
+
+#[test]
+fn cmyk_output() {
+    let tree = read_svg(CMYK_SVG);
+
+    let cmyk_profile = std::fs::read("icc/naive-cmyk.icc").unwrap();
+    let options = ConversionOptions {
+        cmyk_profile: Some(cmyk_profile),
+        ..ConversionOptions::default()
+    };
+    let pdf = convert_tree(&tree, options).unwrap();
+    assert!(contains(&pdf, b"/S /GTS_PDFX"));
+    assert!(contains(&pdf, b"/Info (Naive CMYK)"));
+    assert!(contains(&pdf, b"/N 4"));
+    // The mask is blended in the output color space, so no sRGB profile is needed.
+    assert!(!contains(&pdf, b"/N 3"));
+
+    // An RGB profile can't be used as the output profile.
+    let srgb_profile = std::fs::read("../src/icc/sRGB-v4.icc").unwrap();
+    let options = ConversionOptions {
+        cmyk_profile: Some(srgb_profile),
+        ..ConversionOptions::default()
+    };
+    let result = convert_tree(&tree, options);
+    assert!(matches!(result, Err(ConversionError::InvalidColorProfile(_))));
+}
+
+// reference image is not part of test suite yet
+#[ignore]
+#[test]
+fn cmyk_output_rendering() {
+    let cmyk_profile = std::fs::read("icc/naive-cmyk.icc").unwrap();
+    let options = ConversionOptions {
+        cmyk_profile: Some(cmyk_profile),
+        ..ConversionOptions::default()
+    };
+    assert_eq!(run_svg_test(CMYK_SVG, options, "api/cmyk_output"), 0);
+}
+
+#[allow(dead_code)]
+fn vector_filter_svg(primitive: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <filter id="shadow">
+                {primitive}
+                <feMerge><feMergeNode/><feMergeNode in="SourceGraphic"/></feMerge>
+            </filter>
+            <rect width="50" height="50" fill="blue" filter="url(#shadow)"/>
+        </svg>"#
+    )
+}
+
+#[test]
+fn vector_filter() {
+    let is_rasterized = |primitive: &str| {
+        let options =
+            ConversionOptions { compress: false, ..ConversionOptions::default() };
+        let pdf = convert_with(&vector_filter_svg(primitive), options);
+        contains(&pdf, b"/Subtype /Image")
+    };
+
+    // A flat drop shadow can be drawn as vector content...
+    assert!(!is_rasterized(r#"<feOffset in="SourceAlpha" dx="5" dy="5"/>"#));
+    // ... but a blurred one needs to be rasterized.
+    assert!(is_rasterized(r#"<feGaussianBlur in="SourceAlpha" stdDeviation="5"/>"#));
+}
+
+// reference image is not part of test suite yet
+#[ignore]
+#[test]
+fn vector_filter_rendering() {
+    let svg = vector_filter_svg(r#"<feOffset in="SourceAlpha" dx="5" dy="5"/>"#);
+    let res = run_svg_test(&svg, ConversionOptions::default(), "api/vector_filter");
+    assert_eq!(res, 0);
+}
+
+#[test]
+fn huge_filter_region() {
+    let svg =
+        std::fs::read_to_string("svg/resvg/filters/filter/huge-region.svg").unwrap();
+    let options = ConversionOptions {
+        max_pixmap_area: 100 * 100,
+        compress: false,
+        ..ConversionOptions::default()
+    };
+
+    let pdf = convert_with(&svg, options);
+    let pdf = String::from_utf8_lossy(&pdf);
+    // The filter is rasterized, but into no more pixels than allowed.
+    let dimension = |key: &str| -> u32 {
+        let start = pdf.find(key).unwrap() + key.len();
+        pdf[start..].lines().next().unwrap().trim().parse().unwrap()
+    };
+    assert!(dimension("/Width ") * dimension("/Height ") <= 100 * 100);
+}
+
+#[test]
+fn filter_visible_area() {
+    let svg = |clip_x: f32| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                <clipPath id="clip"><rect x="{clip_x}" width="10" height="20"/></clipPath>
+                <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+                <g clip-path="url(#clip)">
+                    <rect width="1000" height="1000" fill="blue" filter="url(#blur)"/>
+                </g>
+            </svg>"#
+        )
+    };
+    let convert = |clip_x: f32| {
+        let options =
+            ConversionOptions { raster_scale: 1.0, ..ConversionOptions::default() };
+        convert_with(&svg(clip_x), options)
+    };
+
+    // Only the part of the filter region inside of the clip path is rasterized.
+    let pdf = convert(0.0);
+    assert!(contains(&pdf, b"/Width 10"));
+    assert!(contains(&pdf, b"/Height 20"));
+
+    // If the clip path is outside of the canvas, nothing is rasterized at all.
+    let pdf = convert(200.0);
+    assert!(!contains(&pdf, b"/Subtype /Image"));
+}
+
+#[test]
+fn zero_max_pixmap_area() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+        <rect width="10" height="20" fill="blue" filter="url(#blur)"/>
+    </svg>"#;
+
+    // The filter is still rendered, into a single pixel.
+    let options =
+        ConversionOptions { max_pixmap_area: 0, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    assert!(contains(&pdf, b"/Width 1\n"));
+    assert!(contains(&pdf, b"/Height 1\n"));
+}
+
+#[test]
+fn raster_jpeg_quality() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+        <rect x="20" y="20" width="60" height="60" fill="blue" filter="url(#blur)"/>
+    </svg>"#;
+
+    let options = ConversionOptions {
+        raster_jpeg_quality: Some(80),
+        ..ConversionOptions::default()
+    };
+    let pdf = convert_with(svg, options);
+    assert!(contains(&pdf, b"/Filter /DCTDecode"));
+    // The alpha channel is still written losslessly.
+    assert!(contains(&pdf, b"/SMask"));
+}
+
+#[test]
+fn png_passthrough() {
+    // A 2x2 RGB PNG whose scanlines use the `Sub` filter.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="100" height="100" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+    </svg>"#;
+
+    let pdf = convert_with(svg, ConversionOptions::default());
+    assert!(contains(&pdf, b"/Predictor 15"));
+    assert!(contains(&pdf, b"/Colors 3"));
+    assert!(contains(&pdf, b"/Columns 2"));
+}
+
+#[test]
+fn deduplicate_images() {
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+        <image id="png" width="10" height="10" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+        <use xlink:href="#png" x="20"/>
+        <use xlink:href="#png" x="40"/>
+        <image id="svg" y="50" width="10" height="10" href="data:image/svg+xml;utf8,%3Csvg xmlns='http://www.w3.org/2000/svg' width='10' height='10'%3E%3Crect width='5' height='5'/%3E%3C/svg%3E"/>
+        <use xlink:href="#svg" x="20"/>
+        <image y="70" width="10" height="10" href="data:image/svg+xml;utf8,%3Csvg xmlns='http://www.w3.org/2000/svg' width='10' height='10'%3E%3Ccircle r='5'/%3E%3C/svg%3E"/>
+        <image y="90" width="10" height="10" href="data:image/svg+xml;utf8,%3Csvg xmlns='http://www.w3.org/2000/svg' width='20' height='20'%3E%3Crect width='5' height='5'/%3E%3C/svg%3E"/>
+    </svg>"##;
+
+    let pdf = convert_with(svg, ConversionOptions::default());
+    assert_eq!(count(&pdf, b"/Subtype /Image"), 1);
+    // The SVG image and its copy, one with the same size but a different content and
+    // one with a different size.
+    assert_eq!(count(&pdf, b"/Subtype /Form"), 3);
+}
+
+#[test]
+fn max_image_dpi() {
+    // The same 2x2 PNG, once placed at a size of 1x1pt and once at 10x10pt.
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+        <image id="png" width="1" height="1" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+        <use xlink:href="#png" transform="translate(20 20) scale(10)"/>
+    </svg>"##;
+
+    let options = ConversionOptions { max_image_dpi: Some(72.0), ..Default::default() };
+    let pdf = convert_with(svg, options);
+    assert_eq!(count(&pdf, b"/Subtype /Image"), 2);
+    assert_eq!(count(&pdf, b"/Width 1"), 1);
+    assert_eq!(count(&pdf, b"/Width 2"), 1);
+}
+
+#[test]
+fn bmp_image() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="100" height="100" href="data:image/bmp;base64,Qk1GAAAAAAAAADYAAAAoAAAAAgAAAAIAAAABABgAAAAAABAAAAATCwAAEwsAAAAAAAAAAAAAAAD/AP8AAAAAAP8A/wAAAA=="/>
+    </svg>"#;
+    let options = usvg::Options {
+        image_href_resolver: svg2pdf::image_href_resolver(),
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options, &FONTDB.lock().unwrap()).unwrap();
+
+    let pdf = convert_tree(&tree, ConversionOptions::default()).unwrap();
+    assert!(contains(&pdf, b"/Subtype /Image"));
+    assert!(contains(&pdf, b"/Width 2"));
+}
+
+#[test]
+fn pixelated_image() {
+    // A 2x2 PNG that is drawn at a size of 10x10pt, once with and once without
+    // smoothing.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="10" height="10" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+        <image x="20" width="10" height="10" image-rendering="optimizeSpeed" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+    </svg>"#;
+
+    let options = ConversionOptions {
+        pixelated_image_dpi: Some(72.0),
+        ..Default::default()
+    };
+    let pdf = convert_with(svg, options);
+    assert_eq!(count(&pdf, b"/Interpolate true"), 1);
+    assert_eq!(count(&pdf, b"/Interpolate false"), 1);
+    // The pixelated image is scaled up by a factor of 5.
+    assert_eq!(count(&pdf, b"/Width 2"), 1);
+    assert_eq!(count(&pdf, b"/Width 10"), 1);
+}
+
+#[test]
+fn pixelated_image_limits() {
+    // A 2x2 PNG that is drawn at a size of 100x100pt.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="100" height="100" image-rendering="optimizeSpeed" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+    </svg>"#;
+    let tree = read_svg(svg);
+
+    let width = |options: ConversionOptions| {
+        let pdf = convert_tree(&tree, options).unwrap();
+        let start = pdf.windows(7).position(|w| w == b"/Width ").unwrap() + 7;
+        let end = start + pdf[start..].iter().position(|b| *b == b'\n').unwrap();
+        std::str::from_utf8(&pdf[start..end]).unwrap().parse::<u32>().unwrap()
+    };
+
+    // 1000 dpi would need a factor of 695, but 300 dpi only allow one of 208.
+    let options = ConversionOptions {
+        pixelated_image_dpi: Some(1000.0),
+        max_image_dpi: Some(300.0),
+        ..Default::default()
+    };
+    assert_eq!(width(options), 416);
+
+    // The image may not have more than 100x100 pixels.
+    let options = ConversionOptions {
+        pixelated_image_dpi: Some(1000.0),
+        max_pixmap_area: 100 * 100,
+        ..Default::default()
+    };
+    assert_eq!(width(options), 100);
+}
+
+#[test]
+fn compact_images() {
+    // A 1-bit palette PNG with a transparent entry and an RGBA PNG with three colors
+    // and binary alpha.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <image width="10" height="10" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAQAAAACAQMAAABFZu8gAAAABlBMVEX/AAAAAP9sof2OAAAAAnRSTlMA/1uRIrUAAAAMSURBVHicYwhgWAAAAZQA8XIy3VUAAAAASUVORK5CYII="/>
+        <image x="20" width="10" height="10" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAMAAAABCAYAAAAb4BS0AAAAEklEQVR4nGP4z8DwHwiB4P9/AB7uBPwGAuOQAAAAAElFTkSuQmCC"/>
+    </svg>"#;
+
+    let pdf = convert_with(svg, ConversionOptions::default());
+    assert!(contains(&pdf, b"/Indexed [/ICCBased"));
+    assert!(contains(&pdf, b"/Mask [0 0]"));
+    assert!(contains(&pdf, b"/ImageMask true"));
+    assert!(contains(&pdf, b"/BitsPerComponent 2"));
+    assert!(!contains(&pdf, b"/SMask"));
+}
+
+#[test]
+fn font_cache() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <text x="10" y="50" font-family="Noto Sans" font-size="20">Cached</text>
+    </svg>"#;
+    let tree = read_svg(svg);
+
+    let cache = svg2pdf::FontCache::new();
+    let options = ConversionOptions {
+        font_cache: Some(cache.clone()),
+        ..ConversionOptions::default()
+    };
+    let convert = |options: ConversionOptions| convert_tree(&tree, options).unwrap();
+
+    // The cache is shared between threads and doesn't change the output.
+    let uncached = convert(ConversionOptions::default());
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| assert_eq!(convert(options.clone()), uncached));
+        }
+    });
+
+    cache.clear();
+    assert_eq!(convert(options), uncached);
+}
+
+#[test]
+fn text_runs() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <text x="10" y="50" font-family="Noto Sans" font-size="20" letter-spacing="2">AVA Text</text>
+    </svg>"#;
+
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    // The whole span is shown with a single text object and operator, with the
+    // letter spacing and the kerning as adjustments.
+    assert_eq!(count(&pdf, b"BT\n"), 1);
+    assert_eq!(count(&pdf, b" Tm\n"), 1);
+    assert_eq!(count(&pdf, b"] TJ\n"), 1);
+    assert_eq!(count(&pdf, b" -60 ("), 2);
+    assert_eq!(count(&pdf, b" -100 ("), 3);
+}
+
+#[test]
+fn actual_text() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <text x="10" y="50" font-family="Noto Sans" font-size="20">office</text>
+        <text x="10" y="80" font-family="Noto Sans Devanagari" font-size="20">कि</text>
+    </svg>"#;
+
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    // The ligature and the reordered cluster are marked with their original text.
+    assert_eq!(count(&pdf, b"/ActualText (ffi)"), 1);
+    assert_eq!(count(&pdf, b"/ActualText <FEFF0915093F>"), 1);
+    assert_eq!(count(&pdf, b"BDC"), 2);
+    assert_eq!(count(&pdf, b"EMC"), 2);
+}
+
+#[test]
+fn tagged() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <rect width="200" height="100" fill="gray"/>
+        <text x="10" y="30" font-family="Noto Sans">Hello <tspan fill="red">world</tspan></text>
+        <g id="chart">
+            <title>A bar chart</title>
+            <rect x="10" y="40" width="20" height="50"/>
+        </g>
+        <g opacity="0.5"><text x="100" y="30" font-family="Noto Sans">Faded</text></g>
+    </svg>"#;
+    let alt_texts = svg2pdf::alt_texts_from_svg(svg);
+    assert_eq!(alt_texts, HashMap::from([("chart".into(), "A bar chart".into())]));
+
+    let options = ConversionOptions {
+        tagged: true,
+        alt_texts,
+        ..ConversionOptions::default()
+    };
+    let pdf = convert_with(svg, options);
+    assert_eq!(count(&pdf, b"/StructTreeRoot"), 2);
+    assert_eq!(count(&pdf, b"/Marked true"), 1);
+    assert_eq!(count(&pdf, b"/S /P\n"), 2);
+    assert_eq!(count(&pdf, b"/S /Span"), 3);
+    assert_eq!(count(&pdf, b"/Alt (A bar chart)"), 1);
+    assert_eq!(count(&pdf, b"/Artifact BMC"), 1);
+    // The faded text is tagged inside of its XObject.
+    assert_eq!(count(&pdf, b"/StructParents"), 2);
+    assert_eq!(count(&pdf, b"/Type /MCR"), 1);
+}
+
+#[test]
+fn tagged_artifacts() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <pattern id="pattern" width="10" height="10" patternUnits="userSpaceOnUse">
+            <rect width="5" height="5"/>
+        </pattern>
+        <filter id="shadow">
+            <feOffset in="SourceAlpha" dx="2" dy="2"/>
+            <feMerge><feMergeNode/><feMergeNode in="SourceGraphic"/></feMerge>
+        </filter>
+        <rect width="200" height="100" fill="url(#pattern)"/>
+        <image width="10" height="10" href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEklEQVR4nGP8z8DA8J+BEUIBAB4FA/8/Wdo/AAAAAElFTkSuQmCC"/>
+        <text x="10" y="50" font-family="Noto Sans" filter="url(#shadow)">Shadow</text>
+    </svg>"#;
+    let options = ConversionOptions {
+        tagged: true,
+        compress: false,
+        ..ConversionOptions::default()
+    };
+    let pdf = convert_with(svg, options);
+    // The rect, the content of its pattern, the image without an alternative text
+    // and the shadow are artifacts.
+    assert_eq!(count(&pdf, b"/Artifact BMC"), 4);
+    assert_eq!(count(&pdf, b"/S /Figure"), 0);
+    // The text is tagged inside of the XObject of the filter source, while the page
+    // itself only contains artifacts.
+    assert_eq!(count(&pdf, b"/S /P\n"), 1);
+    assert_eq!(count(&pdf, b"/StructParents"), 1);
+    assert_eq!(count(&pdf, b"/Type /MCR"), 1);
+}
+
+#[test]
+fn tagged_filter_source() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <filter id="double">
+            <feOffset dx="2" dy="2" result="copy"/>
+            <feMerge><feMergeNode in="copy"/><feMergeNode in="SourceGraphic"/></feMerge>
+        </filter>
+        <text x="10" y="50" font-family="Noto Sans" filter="url(#double)">Twice</text>
+    </svg>"#;
+    let options = ConversionOptions {
+        tagged: true,
+        compress: false,
+        ..ConversionOptions::default()
+    };
+    let pdf = convert_with(svg, options);
+    // The text is drawn twice, but only the topmost copy is tagged, while the other
+    // one is drawn from an untagged XObject as an artifact. The third XObject is
+    // the one of the group.
+    assert_eq!(count(&pdf, b"/Subtype /Form"), 3);
+    assert_eq!(count(&pdf, b"/Artifact BMC"), 1);
+    assert_eq!(count(&pdf, b"/StructParents"), 1);
+    assert_eq!(count(&pdf, b"/Type /MCR"), 1);
+}
+
+#[allow(dead_code)]
+const VERTICAL_TEXT_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+    <text x="50" y="20" font-family="'M PLUS 1p'" font-size="20" writing-mode="tb">日本語ABC</text>
+</svg>"#;
+
+#[test]
+fn vertical_text() {
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(VERTICAL_TEXT_SVG, options);
+    // The upright glyphs are shown vertically, while the sideways ones are shown
+    // horizontally with the same CID font.
+    assert_eq!(count(&pdf, b"/Encoding /Identity-H"), 1);
+    assert_eq!(count(&pdf, b"/Encoding /Identity-V"), 1);
+    assert_eq!(count(&pdf, b"/DescendantFonts"), 2);
+    assert_eq!(count(&pdf, b"/W2 ["), 1);
+    assert_eq!(count(&pdf, b" [-1000 500 860]"), 3);
+    assert_eq!(count(&pdf, b"] TJ\n"), 2);
+}
+
+// reference image is not part of test suite yet
+#[ignore]
+#[test]
+fn vertical_text_rendering() {
+    let res = run_svg_test(
+        VERTICAL_TEXT_SVG,
+        ConversionOptions::default(),
+        "api/vertical_text",
+    );
+    assert_eq!(res, 0);
+}
+
+#[test]
+fn text_in_pattern_of_text() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <pattern id="pattern" width="20" height="20" patternUnits="userSpaceOnUse">
+            <text x="2" y="15" font-family="Noto Serif" font-size="10">C</text>
+        </pattern>
+        <text x="10" y="60" font-family="Noto Sans" font-size="40" fill="url(#pattern)">AB</text>
+    </svg>"#;
+
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    // The text in the pattern is shown while the outer text is painted.
+    assert_eq!(count(&pdf, b"] TJ\n"), 2);
+}
+
+#[test]
+fn color_glyphs() {
+    // A COLR, an SVG and an sbix glyph, followed by a plain outline glyph.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <text x="10" y="60" font-family="Color Test" font-size="40" fill="blue">ABCD</text>
+    </svg>"#;
+
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    // The color glyphs end up in a Type 3 font, ...
+    assert_eq!(count(&pdf, b"/Subtype /Type3"), 1);
+    assert_eq!(count(&pdf, b"/Differences [0 /g1 /g2 /g3]"), 1);
+    assert_eq!(count(&pdf, b"<00> <0041>\n<01> <0042>\n<02> <0043>"), 1);
+    // ... which draws the translucent COLR layer, the gradient of the SVG glyph and
+    // the image of the sbix glyph, ...
+    assert_eq!(count(&pdf, b"/ca 0.5019608"), 1);
+    assert_eq!(count(&pdf, b"/ShadingType 2"), 1);
+    assert_eq!(count(&pdf, b"/Subtype /Image"), 2);
+    // ... while the plain glyph stays in the CID font.
+    assert_eq!(count(&pdf, b"/Subtype /Type0"), 1);
+    assert_eq!(count(&pdf, b"<0004> <0044>"), 1);
+}
+
+#[test]
+fn spread_method_periods() {
+    let svg = |x2: f32| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10000" height="100">
+                <linearGradient id="lg" x1="0" x2="{x2}" gradientUnits="userSpaceOnUse" spreadMethod="reflect">
+                    <stop offset="0" stop-color="red"/>
+                    <stop offset="1" stop-color="blue"/>
+                </linearGradient>
+                <rect width="10000" height="100" fill="url(#lg)"/>
+            </svg>"#
+        )
+    };
+    let convert = |x2: f32| {
+        let options =
+            ConversionOptions { compress: false, ..ConversionOptions::default() };
+        String::from_utf8_lossy(&convert_with(&svg(x2), options)).into_owned()
+    };
+
+    // The shading is extended over the whole rectangle and the gradient is tiled
+    // over all of the 2000 periods in it, so that no part of it is padded.
+    let pdf = convert(5.0);
+    assert!(pdf.contains("/Coords [0 0 10000 0]"));
+    assert_eq!(pdf.matches("/Domain [0 2000]").count(), 2);
+
+    // A gradient that would need a million periods is only tiled over a part of
+    // the rectangle.
+    let pdf = convert(0.01);
+    assert!(pdf.contains("/Coords [0 0 81.92 0]"));
+    assert_eq!(pdf.matches("/Domain [0 8192]").count(), 2);
+}
+
+#[test]
+fn variable_font_default_instance() {
+    // At the maximum weight, the glyph of the font is 200 units wider.
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+        <text x="10" y="60" font-family="Variable Test" font-weight="900" font-size="40"
+            style="font-variation-settings: 'wght' 900">AA</text>
+    </svg>"#;
+
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(svg, options);
+    // The text is laid out with the advances of the default instance, which match
+    // the widths of the embedded font, so no adjustments are needed.
+    assert_eq!(count(&pdf, b"/W [0 1 600]"), 1);
+    assert_eq!(count(&pdf, b"[(\\000\\001\\000\\001)] TJ"), 1);
+}
+
+#[allow(dead_code)]
+const VARIABLE_CFF2_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+    <text x="10" y="60" font-family="Variable CFF2 Test" font-weight="900" font-size="40"
+        style="font-variation-settings: 'wght' 900">AA</text>
+</svg>"#;
+
+#[test]
+fn variable_cff2_font_default_instance() {
+    let options = ConversionOptions { compress: false, ..ConversionOptions::default() };
+    let pdf = convert_with(VARIABLE_CFF2_SVG, options);
+    assert_eq!(count(&pdf, b"/W [0 1 600]"), 1);
+
+    // The CFF2 outlines are embedded as a bare CFF font program, which is always
+    // compressed.
+    let subtype = b"/Subtype /CIDFontType0C";
+    let start = pdf.windows(subtype.len()).position(|w| w == subtype).unwrap();
+    let start = start + pdf[start..].windows(7).position(|w| w == b"stream\n").unwrap();
+    let end = start + pdf[start..].windows(9).position(|w| w == b"endstream").unwrap();
+    let data =
+        miniz_oxide::inflate::decompress_to_vec_zlib(&pdf[start + 7..end - 1]).unwrap();
+
+    // Its glyphs have the CIDs of their glyph ids and the outlines of the default
+    // instance.
+    let cff = ttf_parser::cff::Table::parse(&data).unwrap();
+    let glyph = ttf_parser::GlyphId(1);
+    assert_eq!(cff.number_of_glyphs(), 2);
+    assert_eq!(cff.glyph_cid(glyph), Some(1));
+
+    struct Points(Vec<(f32, f32)>);
+    impl ttf_parser::OutlineBuilder for Points {
+        fn move_to(&mut self, x: f32, y: f32) {
+            self.0.push((x, y));
+        }
+        fn line_to(&mut self, x: f32, y: f32) {
+            self.0.push((x, y));
+        }
+        fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {
+            unreachable!()
+        }
+        fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+            unreachable!()
+        }
+        fn close(&mut self) {}
+    }
+
+    let mut points = Points(vec![]);
+    cff.outline(glyph, &mut points).unwrap();
+    assert_eq!(points.0, [(50.0, 0.0), (50.0, 700.0), (550.0, 700.0), (550.0, 0.0)]);
+}
+
+// reference image is not part of test suite yet
+#[ignore]
+#[test]
+fn variable_cff2_font_rendering() {
+    let res = run_svg_test(
+        VARIABLE_CFF2_SVG,
+        ConversionOptions::default(),
+        "api/variable_cff2_font",
+    );
+    assert_eq!(res, 0);
+}
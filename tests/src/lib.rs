@@ -68,6 +68,34 @@ pub fn read_svg(svg_string: &str) -> Tree {
     Tree::from_str(svg_string, &options, &FONTDB.lock().unwrap()).unwrap()
 }
 
+/// Converts a usvg Tree into a PDF with the default page options.
+pub fn convert_tree(tree: &Tree, options: ConversionOptions) -> svg2pdf::Result<Vec<u8>> {
+    svg2pdf::to_pdf(tree, options, PageOptions::default(), &FONTDB.lock().unwrap())
+}
+
+/// Converts an SVG string into a PDF with the default page options.
+pub fn convert_with(svg: &str, options: ConversionOptions) -> Vec<u8> {
+    convert_tree(&read_svg(svg), options).unwrap()
+}
+
+/// Counts how often a byte string occurs in a PDF.
+pub fn count(pdf: &[u8], needle: &[u8]) -> usize {
+    pdf.windows(needle.len()).filter(|w| *w == needle).count()
+}
+
+/// Checks whether a byte string occurs in a PDF.
+pub fn contains(pdf: &[u8], needle: &[u8]) -> bool {
+    count(pdf, needle) > 0
+}
+
+/// Converts an SVG string into a PDF and compares its rendered version with the
+/// reference image of a test.
+pub fn run_svg_test(svg: &str, options: ConversionOptions, test_name: &str) -> i32 {
+    let pdf = convert_with(svg, options);
+    let actual_image = render_pdf(&pdf);
+    run_test_impl(pdf, actual_image, test_name)
+}
+
 /// Converts an image into a PDF and returns the PDF as well as a rendered version
 /// of it.
 pub fn convert_svg(
@@ -78,7 +106,8 @@ pub fn convert_svg(
     let svg = fs::read_to_string(svg_path).unwrap();
     let tree = read_svg(&svg);
     let pdf =
-        svg2pdf::to_pdf(&tree, conversion_options, page_options, &FONTDB.lock().unwrap());
+        svg2pdf::to_pdf(&tree, conversion_options, page_options, &FONTDB.lock().unwrap())
+            .unwrap();
     let image = render_pdf(pdf.as_slice());
     (pdf, image)
 }
@@ -281,7 +281,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_filters_filter_with_transform_outside_of_canvas() {assert_eq!(run_test("resvg/filters/filter/with-transform-outside-of-canvas"), 0)}
 #[test] fn resvg_filters_filter_in_SourceAlpha() {assert_eq!(run_test("resvg/filters/filter/in=SourceAlpha"), 0)}
 #[test] fn resvg_filters_filter_on_a_vertical_line() {assert_eq!(run_test("resvg/filters/filter/on-a-vertical-line"), 0)}
-// will sigkill
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_filters_filter_huge_region() {assert_eq!(run_test("resvg/filters/filter/huge-region"), 0)}
 #[test] fn resvg_filters_filter_invalid_primitive_1() {assert_eq!(run_test("resvg/filters/filter/invalid-primitive-1"), 0)}
 #[test] fn resvg_filters_filter_in_to_invalid_2() {assert_eq!(run_test("resvg/filters/filter/in-to-invalid-2"), 0)}
@@ -444,7 +444,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_paint_servers_radialGradient_gradientTransform() {assert_eq!(run_test("resvg/paint-servers/radialGradient/gradientTransform"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_gradientUnits_userSpaceOnUse() {assert_eq!(run_test("resvg/paint-servers/radialGradient/gradientUnits=userSpaceOnUse"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_zero_r_with_stop_opacity_2() {assert_eq!(run_test("resvg/paint-servers/radialGradient/zero-r-with-stop-opacity-2"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_radialGradient_attributes_via_xlink_href_from_linearGradient() {assert_eq!(run_test("resvg/paint-servers/radialGradient/attributes-via-xlink-href-from-linearGradient"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_zero_r_with_stop_opacity_1() {assert_eq!(run_test("resvg/paint-servers/radialGradient/zero-r-with-stop-opacity-1"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_stops_via_xlink_href_complex_order() {assert_eq!(run_test("resvg/paint-servers/radialGradient/stops-via-xlink-href-complex-order"), 0)}
@@ -455,17 +455,17 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_paint_servers_radialGradient_recursive_xlink_href() {assert_eq!(run_test("resvg/paint-servers/radialGradient/recursive-xlink-href"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_negative_r() {assert_eq!(run_test("resvg/paint-servers/radialGradient/negative-r"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_focal_point_correction() {assert_eq!(run_test("resvg/paint-servers/radialGradient/focal-point-correction"), 0)}
-// spreadMethod repeat not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_radialGradient_spreadMethod_repeat() {assert_eq!(run_test("resvg/paint-servers/radialGradient/spreadMethod=repeat"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_unresolved_xlink_href() {assert_eq!(run_test("resvg/paint-servers/radialGradient/unresolved-xlink-href"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_invalid_xlink_href() {assert_eq!(run_test("resvg/paint-servers/radialGradient/invalid-xlink-href"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_attributes_via_xlink_href_from_rect() {assert_eq!(run_test("resvg/paint-servers/radialGradient/attributes-via-xlink-href-from-rect"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_radialGradient_attributes_via_xlink_href_complex_order() {assert_eq!(run_test("resvg/paint-servers/radialGradient/attributes-via-xlink-href-complex-order"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_xlink_href_not_to_gradient() {assert_eq!(run_test("resvg/paint-servers/radialGradient/xlink-href-not-to-gradient"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_gradientUnits_userSpaceOnUse_with_percent() {assert_eq!(run_test("resvg/paint-servers/radialGradient/gradientUnits=userSpaceOnUse-with-percent"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_self_recursive_xlink_href() {assert_eq!(run_test("resvg/paint-servers/radialGradient/self-recursive-xlink-href"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_radialGradient_spreadMethod_reflect() {assert_eq!(run_test("resvg/paint-servers/radialGradient/spreadMethod=reflect"), 0)}
 #[test] fn resvg_paint_servers_radialGradient_default_attributes() {assert_eq!(run_test("resvg/paint-servers/radialGradient/default-attributes"), 0)}
 #[test] fn resvg_paint_servers_stop_opacity_simple_case() {assert_eq!(run_test("resvg/paint-servers/stop-opacity/simple-case"), 0)}
@@ -523,7 +523,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_paint_servers_linearGradient_gradientTransform() {assert_eq!(run_test("resvg/paint-servers/linearGradient/gradientTransform"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_gradientUnits_userSpaceOnUse() {assert_eq!(run_test("resvg/paint-servers/linearGradient/gradientUnits=userSpaceOnUse"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_recursive_xlink_href_3() {assert_eq!(run_test("resvg/paint-servers/linearGradient/recursive-xlink-href-3"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_linearGradient_attributes_via_xlink_href_from_radialGradient() {assert_eq!(run_test("resvg/paint-servers/linearGradient/attributes-via-xlink-href-from-radialGradient"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_recursive_xlink_href_2() {assert_eq!(run_test("resvg/paint-servers/linearGradient/recursive-xlink-href-2"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_recursive_xlink_href_1() {assert_eq!(run_test("resvg/paint-servers/linearGradient/recursive-xlink-href-1"), 0)}
@@ -531,17 +531,17 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_paint_servers_linearGradient_single_stop() {assert_eq!(run_test("resvg/paint-servers/linearGradient/single-stop"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_invalid_gradientTransform() {assert_eq!(run_test("resvg/paint-servers/linearGradient/invalid-gradientTransform"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_stops_via_xlink_href_from_rect() {assert_eq!(run_test("resvg/paint-servers/linearGradient/stops-via-xlink-href-from-rect"), 0)}
-// spreadMethod repeat not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_linearGradient_spreadMethod_repeat() {assert_eq!(run_test("resvg/paint-servers/linearGradient/spreadMethod=repeat"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_unresolved_xlink_href() {assert_eq!(run_test("resvg/paint-servers/linearGradient/unresolved-xlink-href"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_invalid_xlink_href() {assert_eq!(run_test("resvg/paint-servers/linearGradient/invalid-xlink-href"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_attributes_via_xlink_href_from_rect() {assert_eq!(run_test("resvg/paint-servers/linearGradient/attributes-via-xlink-href-from-rect"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_single_stop_with_opacity_used_by_fill_and_stroke() {assert_eq!(run_test("resvg/paint-servers/linearGradient/single-stop-with-opacity-used-by-fill-and-stroke"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_linearGradient_attributes_via_xlink_href_complex_order() {assert_eq!(run_test("resvg/paint-servers/linearGradient/attributes-via-xlink-href-complex-order"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_gradientUnits_userSpaceOnUse_with_percent() {assert_eq!(run_test("resvg/paint-servers/linearGradient/gradientUnits=userSpaceOnUse-with-percent"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_self_recursive_xlink_href() {assert_eq!(run_test("resvg/paint-servers/linearGradient/self-recursive-xlink-href"), 0)}
-// spreadMethod reflect not supported
+// reference image is not part of test suite yet
 #[ignore] #[test] fn resvg_paint_servers_linearGradient_spreadMethod_reflect() {assert_eq!(run_test("resvg/paint-servers/linearGradient/spreadMethod=reflect"), 0)}
 #[test] fn resvg_paint_servers_linearGradient_default_attributes() {assert_eq!(run_test("resvg/paint-servers/linearGradient/default-attributes"), 0)}
 #[test] fn resvg_paint_servers_stop_color_simple_case() {assert_eq!(run_test("resvg/paint-servers/stop-color/simple-case"), 0)}
@@ -1238,7 +1238,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_text_text_escaped_text_2() {assert_eq!(run_test("resvg/text/text/escaped-text-2"), 0)}
 #[test] fn resvg_text_text_dx_and_dy_instead_of_x_and_y() {assert_eq!(run_test("resvg/text/text/dx-and-dy-instead-of-x-and-y"), 0)}
 #[test] fn resvg_text_text_rotate() {assert_eq!(run_test("resvg/text/text/rotate"), 0)}
-// need to investigate
+// font is not part of test suite yet
 #[ignore] #[test] fn resvg_text_text_compound_emojis_and_coordinates_list() {assert_eq!(run_test("resvg/text/text/compound-emojis-and-coordinates-list"), 0)}
 #[test] fn resvg_text_text_escaped_text_3() {assert_eq!(run_test("resvg/text/text/escaped-text-3"), 0)}
 #[test] fn resvg_text_text_escaped_text_1() {assert_eq!(run_test("resvg/text/text/escaped-text-1"), 0)}
@@ -1254,7 +1254,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_text_text_x_and_y_with_dx_and_dy() {assert_eq!(run_test("resvg/text/text/x-and-y-with-dx-and-dy"), 0)}
 #[test] fn resvg_text_text_real_text_height() {assert_eq!(run_test("resvg/text/text/real-text-height"), 0)}
 #[test] fn resvg_text_text_dx_and_dy_with_more_values_than_characters() {assert_eq!(run_test("resvg/text/text/dx-and-dy-with-more-values-than-characters"), 0)}
-// need to investigate
+// font is not part of test suite yet
 #[ignore] #[test] fn resvg_text_text_emojis() {assert_eq!(run_test("resvg/text/text/emojis"), 0)}
 #[test] fn resvg_text_text_x_and_y_with_multiple_values_and_arabic_text() {assert_eq!(run_test("resvg/text/text/x-and-y-with-multiple-values-and-arabic-text"), 0)}
 #[test] fn resvg_text_text_simple_case() {assert_eq!(run_test("resvg/text/text/simple-case"), 0)}
@@ -1263,7 +1263,7 @@ pub fn run_test(test_name: &str) -> i32 {
 #[test] fn resvg_text_text_complex_grapheme_split_by_tspan() {assert_eq!(run_test("resvg/text/text/complex-grapheme-split-by-tspan"), 0)}
 #[test] fn resvg_text_text_dx_and_dy_with_less_values_than_characters() {assert_eq!(run_test("resvg/text/text/dx-and-dy-with-less-values-than-characters"), 0)}
 #[test] fn resvg_text_text_percent_value_on_x_and_y() {assert_eq!(run_test("resvg/text/text/percent-value-on-x-and-y"), 0)}
-// need to investigate
+// font is not part of test suite yet
 #[ignore] #[test] fn resvg_text_text_compound_emojis() {assert_eq!(run_test("resvg/text/text/compound-emojis"), 0)}
 #[test] fn resvg_text_text_xml_space() {assert_eq!(run_test("resvg/text/text/xml-space"), 0)}
 #[test] fn resvg_text_text_fill_rule_evenodd() {assert_eq!(run_test("resvg/text/text/fill-rule=evenodd"), 0)}